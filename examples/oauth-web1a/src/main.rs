@@ -34,7 +34,7 @@ fn oauth_client() -> OAuth1aClient {
 async fn root(cookies: Cookies) -> impl IntoResponse {
     let oauth = oauth_client();
     let request_token = oauth.request_token(None).await.unwrap();
-    cookies.add(Cookie::new(OAUTH_TOKEN_SECRET, request_token.response.oauth_token_secret.clone()));
+    cookies.add(Cookie::new(OAUTH_TOKEN_SECRET, request_token.response.oauth_token_secret.expose().clone()));
     Html(format!("<a href='{}'>oauth<a>", request_token.url)).into_response()
 }
 
@@ -0,0 +1,554 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ::axum::{
+    Router,
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+};
+
+use crate::error::Error;
+
+// Pending logins older than this are discarded
+const PENDING_TTL: Duration = Duration::from_secs(600);
+// /login is unauthenticated, so the pending logins are capped
+const MAX_PENDING: usize = 10_000;
+
+struct PendingStore<V> {
+    entries: Mutex<HashMap<String, (V, Instant)>>,
+    capacity: usize,
+}
+
+impl<V> PendingStore<V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// Discards expired entries and reports whether another one fits.
+    #[cfg_attr(not(feature = "oauth1a"), allow(dead_code))]
+    fn has_room(&self) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (_, created_at)| created_at.elapsed() < PENDING_TTL);
+        entries.len() < self.capacity
+    }
+
+    /// Returns false without inserting when the store is full.
+    fn insert(&self, key: String, value: V) -> bool {
+        // one guard, so concurrent logins cannot push the store past capacity
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (_, created_at)| created_at.elapsed() < PENDING_TTL);
+        if entries.len() >= self.capacity {
+            return false;
+        }
+        entries.insert(key, (value, Instant::now()));
+        true
+    }
+
+    fn take(&self, key: &str) -> Option<V> {
+        let (value, created_at) = self.entries.lock().unwrap().remove(key)?;
+        (created_at.elapsed() < PENDING_TTL).then_some(value)
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, message.to_owned()).into_response()
+}
+
+fn too_many_pending_response() -> Response {
+    error_response(StatusCode::SERVICE_UNAVAILABLE, "too many pending logins")
+}
+
+fn token_error_response(err: Error) -> Response {
+    match err {
+        Error::ClientError(..) => error_response(StatusCode::BAD_REQUEST, "authorization failed"),
+        _ => error_response(StatusCode::BAD_GATEWAY, "token endpoint unavailable"),
+    }
+}
+
+fn redirect_with_cookie(url: &str, cookie_name: &str, value: &str, secure: bool) -> Response {
+    let mut res = Redirect::to(url).into_response();
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}{}",
+        cookie_name,
+        value,
+        PENDING_TTL.as_secs(),
+        if secure { "; Secure" } else { "" }
+    );
+    if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+        res.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    res
+}
+
+fn get_cookie<'a>(headers: &'a HeaderMap, cookie_name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|it| it.to_str().ok())
+        .flat_map(|it| it.split(';'))
+        .filter_map(|it| it.trim().split_once('='))
+        .find(|(name, _)| *name == cookie_name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(feature = "oauth2")]
+pub use self::oauth2::oauth2_router;
+
+#[cfg(feature = "oauth2")]
+mod oauth2 {
+    use super::*;
+    use crate::oauth2::{OAuth2Client, Scope, TokenResult, random_state};
+
+    const STATE_COOKIE: &str = "twapi_oauth2_state";
+
+    struct OAuth2State<S: Scope, F> {
+        client: OAuth2Client<S>,
+        pending: PendingStore<String>,
+        secure: bool,
+        on_token: F,
+    }
+
+    /// Router with `/login` and `/callback` for the OAuth 2.0 Authorization Code Flow.
+    ///
+    /// `/login` redirects to the authorize URL, keeping the PKCE verifier on
+    /// the server and the state in a cookie. `/callback` validates the state,
+    /// exchanges the code and passes the token to `on_token`. The client's
+    /// `redirect_uri` must point at `/callback` wherever the router is mounted.
+    /// The cookie is `Secure` when `redirect_uri` is https.
+    pub fn oauth2_router<S, F, Fut>(client: impl Into<OAuth2Client<S>>, on_token: F) -> Router
+    where
+        S: Scope + Send + Sync + 'static,
+        F: Fn(TokenResult) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: IntoResponse,
+    {
+        let client = client.into();
+        let state = Arc::new(OAuth2State {
+            secure: client.redirect_uri().starts_with("https://"),
+            client,
+            pending: PendingStore::new(MAX_PENDING),
+            on_token,
+        });
+        Router::new()
+            .route("/login", get(login::<S, F>))
+            .route("/callback", get(callback::<S, F, Fut>))
+            .with_state(state)
+    }
+
+    async fn login<S: Scope, F>(State(state): State<Arc<OAuth2State<S, F>>>) -> Response {
+        let oauth_state = random_state();
+        let (url, code_verifier) = state.client.authorize_url(&oauth_state);
+        if !state.pending.insert(oauth_state.clone(), code_verifier) {
+            return too_many_pending_response();
+        }
+        redirect_with_cookie(&url, STATE_COOKIE, &oauth_state, state.secure)
+    }
+
+    async fn callback<S, F, Fut>(
+        State(state): State<Arc<OAuth2State<S, F>>>,
+        Query(params): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+    ) -> Response
+    where
+        S: Scope,
+        F: Fn(TokenResult) -> Fut,
+        Fut: Future,
+        Fut::Output: IntoResponse,
+    {
+        if params.contains_key("error") {
+            return error_response(StatusCode::FORBIDDEN, "authorization denied");
+        }
+        let (Some(code), Some(oauth_state)) = (params.get("code"), params.get("state")) else {
+            return error_response(StatusCode::BAD_REQUEST, "code or state is missing");
+        };
+        if get_cookie(&headers, STATE_COOKIE) != Some(oauth_state.as_str()) {
+            return error_response(StatusCode::BAD_REQUEST, "state mismatch");
+        }
+        let Some(code_verifier) = state.pending.take(oauth_state) else {
+            return error_response(StatusCode::BAD_REQUEST, "unknown or expired state");
+        };
+        match state.client.token(code, &code_verifier).await {
+            Ok((token, _, _)) => (state.on_token)(token).await.into_response(),
+            Err(err) => token_error_response(err),
+        }
+    }
+}
+
+#[cfg(feature = "oauth1a")]
+pub use self::oauth1a::oauth1a_router;
+
+#[cfg(feature = "oauth1a")]
+mod oauth1a {
+    use super::*;
+    use crate::{
+        Secret,
+        oauth1a::{AccessToken, OAuth1aClient},
+    };
+
+    const TOKEN_COOKIE: &str = "twapi_oauth1a_token";
+
+    struct OAuth1aState<F> {
+        client: OAuth1aClient,
+        pending: PendingStore<Secret<String>>,
+        secure: bool,
+        on_token: F,
+    }
+
+    /// Router with `/login` and `/callback` for the OAuth 1.0a three-legged flow.
+    ///
+    /// The request token secret stays on the server. The client's
+    /// `callback_url` must point at `/callback` wherever the router is mounted.
+    /// The cookie is `Secure` when `callback_url` is https. No request token
+    /// is fetched while the pending logins are at their cap.
+    pub fn oauth1a_router<F, Fut>(client: OAuth1aClient, on_token: F) -> Router
+    where
+        F: Fn(AccessToken) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: IntoResponse,
+    {
+        let state = Arc::new(OAuth1aState {
+            secure: client.callback_url().starts_with("https://"),
+            client,
+            pending: PendingStore::new(MAX_PENDING),
+            on_token,
+        });
+        Router::new()
+            .route("/login", get(login::<F>))
+            .route("/callback", get(callback::<F, Fut>))
+            .with_state(state)
+    }
+
+    async fn login<F>(State(state): State<Arc<OAuth1aState<F>>>) -> Response {
+        if !state.pending.has_room() {
+            return too_many_pending_response();
+        }
+        match state.client.request_token(None).await {
+            Ok(request_token) => {
+                let oauth_token = request_token.response.oauth_token;
+                if !state.pending.insert(
+                    oauth_token.clone(),
+                    request_token.response.oauth_token_secret,
+                ) {
+                    return too_many_pending_response();
+                }
+                redirect_with_cookie(&request_token.url, TOKEN_COOKIE, &oauth_token, state.secure)
+            }
+            Err(err) => token_error_response(err),
+        }
+    }
+
+    async fn callback<F, Fut>(
+        State(state): State<Arc<OAuth1aState<F>>>,
+        Query(params): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+    ) -> Response
+    where
+        F: Fn(AccessToken) -> Fut,
+        Fut: Future,
+        Fut::Output: IntoResponse,
+    {
+        if let Some(oauth_token) = params.get("denied") {
+            state.pending.take(oauth_token);
+            return error_response(StatusCode::FORBIDDEN, "authorization denied");
+        }
+        let (Some(oauth_token), Some(oauth_verifier)) =
+            (params.get("oauth_token"), params.get("oauth_verifier"))
+        else {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "oauth_token or oauth_verifier is missing",
+            );
+        };
+        if get_cookie(&headers, TOKEN_COOKIE) != Some(oauth_token.as_str()) {
+            return error_response(StatusCode::BAD_REQUEST, "oauth_token mismatch");
+        }
+        let Some(oauth_token_secret) = state.pending.take(oauth_token) else {
+            return error_response(StatusCode::BAD_REQUEST, "unknown or expired oauth_token");
+        };
+        match state
+            .client
+            .access_token(oauth_token, oauth_token_secret.expose(), oauth_verifier)
+            .await
+        {
+            Ok(access_token) => (state.on_token)(access_token).await.into_response(),
+            Err(err) => token_error_response(err),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "oauth2"))]
+mod tests {
+    use ::axum::{Form, Json, routing::post};
+    use reqwest::redirect::Policy;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::oauth2::{TokenResult, XClient, XScope};
+
+    async fn token(Form(params): Form<Vec<(String, String)>>) -> Json<Value> {
+        assert!(params.contains(&("code".to_owned(), "auth_code".to_owned())));
+        Json(json!({
+            "access_token": "access",
+            "refresh_token": "refresh",
+            "expires_in": 7200,
+            "scope": "tweet.read",
+            "token_type": "bearer",
+        }))
+    }
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { ::axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_router() {
+        let prefix_url = serve(Router::new().route("/2/oauth2/token", post(token))).await;
+        let client = XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/auth/callback",
+            vec![XScope::TweetRead],
+            1,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+            Some(prefix_url),
+        )
+        .unwrap();
+        let app = Router::new().nest(
+            "/auth",
+            oauth2_router(client, |token: TokenResult| async move {
+                token.access_token.expose().clone()
+            }),
+        );
+        let url = serve(app).await;
+        let http = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .unwrap();
+
+        let res = http
+            .get(format!("{}/auth/login", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        let cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap().to_owned();
+        let location =
+            reqwest::Url::parse(res.headers()[header::LOCATION].to_str().unwrap()).unwrap();
+        let oauth_state = location
+            .query_pairs()
+            .find(|(key, _)| key == "state")
+            .unwrap()
+            .1
+            .into_owned();
+
+        let res = http
+            .get(format!("{}/auth/callback?code=auth_code&state=other", url))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = http
+            .get(format!(
+                "{}/auth/callback?code=auth_code&state={}",
+                url, oauth_state
+            ))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await.unwrap(), "access");
+
+        // the state can be used only once
+        let res = http
+            .get(format!(
+                "{}/auth/callback?code=auth_code&state={}",
+                url, oauth_state
+            ))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_router_secure_cookie() {
+        let client = XClient::new(
+            "client",
+            "secret",
+            "https://example.com/callback",
+            vec![XScope::TweetRead],
+        );
+        let url = serve(oauth2_router(client, |_: TokenResult| async {})).await;
+        let http = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .unwrap();
+        let res = http.get(format!("{}/login", url)).send().await.unwrap();
+        let cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.ends_with("; Secure"));
+    }
+
+    #[test]
+    fn test_pending_store_capacity() {
+        let pending = PendingStore::new(2);
+        assert!(pending.insert("a".to_owned(), 1));
+        assert!(pending.insert("b".to_owned(), 2));
+        assert!(!pending.has_room());
+        assert!(!pending.insert("c".to_owned(), 3));
+        assert_eq!(pending.take("a"), Some(1));
+        assert!(pending.insert("c".to_owned(), 3));
+        assert_eq!(pending.take("c"), Some(3));
+    }
+
+    #[test]
+    fn test_pending_store_concurrent_insert() {
+        let pending = Arc::new(PendingStore::new(10));
+        let inserted: usize = (0..8)
+            .map(|thread| {
+                let pending = pending.clone();
+                std::thread::spawn(move || {
+                    (0..10)
+                        .filter(|i| pending.insert(format!("{}-{}", thread, i), *i))
+                        .count()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|it| it.join().unwrap())
+            .sum();
+        assert_eq!(inserted, 10);
+        assert_eq!(pending.entries.lock().unwrap().len(), 10);
+    }
+}
+
+#[cfg(all(test, feature = "oauth1a"))]
+mod oauth1a_tests {
+    use ::axum::routing::post;
+    use reqwest::redirect::Policy;
+
+    use super::*;
+    use crate::oauth1a::{AccessToken, OAuth1aClient};
+
+    async fn request_token() -> &'static str {
+        "oauth_token=request&oauth_token_secret=request_secret&oauth_callback_confirmed=true"
+    }
+
+    async fn access_token(headers: HeaderMap) -> &'static str {
+        let authorization = headers[header::AUTHORIZATION].to_str().unwrap();
+        assert!(authorization.contains("oauth_token=request,"));
+        assert!(authorization.contains("oauth_verifier=verifier"));
+        "oauth_token=access&oauth_token_secret=access_secret&user_id=42&screen_name=alice"
+    }
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { ::axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_oauth1a_router() {
+        let prefix_url = serve(
+            Router::new()
+                .route("/oauth/request_token", post(request_token))
+                .route("/oauth/access_token", post(access_token)),
+        )
+        .await;
+        let client = OAuth1aClient::new_with_options(
+            "key",
+            "secret",
+            "https://example.com/auth/callback",
+            1,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+            Some(prefix_url.clone()),
+        )
+        .unwrap();
+        let app = Router::new().nest(
+            "/auth",
+            oauth1a_router(
+                client,
+                |token: AccessToken| async move { token.screen_name },
+            ),
+        );
+        let url = serve(app).await;
+        let http = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .unwrap();
+
+        let res = http
+            .get(format!("{}/auth/login", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            res.headers()[header::LOCATION],
+            format!("{}/oauth/authorize?oauth_token=request", prefix_url)
+        );
+        let cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.ends_with("; Secure"));
+        let cookie = cookie.split(';').next().unwrap().to_owned();
+        assert_eq!(cookie, "twapi_oauth1a_token=request");
+
+        let res = http
+            .get(format!(
+                "{}/auth/callback?oauth_token=other&oauth_verifier=verifier",
+                url
+            ))
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let callback = format!(
+            "{}/auth/callback?oauth_token=request&oauth_verifier=verifier",
+            url
+        );
+        let res = http
+            .get(&callback)
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await.unwrap(), "alice");
+
+        // the request token can be used only once
+        let res = http
+            .get(&callback)
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = http
+            .get(format!("{}/auth/callback?denied=request", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+}
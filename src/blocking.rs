@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use reqwest::{StatusCode, blocking::RequestBuilder, header::HeaderMap};
+
+use crate::{
+    error::Error,
+    limits::check_blocking,
+    recorder,
+    retry::{Decoder, Step, finish, next_step, send_error},
+};
+
+/// Blocking counterpart of `execute_retry` on reqwest's blocking client.
+///
+/// The limits of an enclosing `CallLimits::run_blocking` are checked before
+/// each attempt and backoff.
+pub(crate) fn execute_retry<T>(
+    endpoint: &'static str,
+    decoder: impl Decoder<T>,
+    f: impl Fn() -> RequestBuilder,
+    try_count: usize,
+    retry_duration: Duration,
+) -> Result<(T, StatusCode, HeaderMap), Error> {
+    let started = Instant::now();
+    let attempts = || {
+        for i in 0..try_count {
+            check_blocking(Duration::ZERO)?;
+            let res = f().send().map_err(|e| send_error(i, e))?;
+            let status = res.status();
+            let headers = res.headers().clone();
+            match next_step(endpoint, i, try_count, retry_duration, status) {
+                Step::Retry(delay) => {
+                    check_blocking(delay)?;
+                    std::thread::sleep(delay);
+                }
+                step => return finish(&decoder, step, res.text(), status, headers),
+            }
+        }
+        unreachable!()
+    };
+    let res = attempts();
+    recorder::record_result(endpoint, started, &res);
+    res
+}
@@ -1,17 +1,177 @@
-use reqwest::{StatusCode, header::HeaderMap};
+use std::time::Duration;
+
+use http::{
+    StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
 use thiserror::Error;
 
+const X_RATE_LIMIT_RESET: &str = "x-rate-limit-reset";
+
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
+    #[cfg(feature = "network")]
     #[error("Reqwest {0}")]
-    Reqwest(#[from] reqwest::Error),
+    Reqwest(Box<reqwest::Error>),
 
     #[error("Invalid {0}")]
     Invalid(String),
 
     #[error("ClientError {0}")]
-    ClientError(String, StatusCode, HeaderMap),
+    ClientError(String, StatusCode, Box<HeaderMap>),
 
     #[error("RetryOver {0}")]
-    RetryOver(String, StatusCode, HeaderMap),
+    RetryOver(String, StatusCode, Box<HeaderMap>),
+
+    /// A successful response whose body could not be decoded. `body` may hold
+    /// live tokens, so it is kept out of `Display`.
+    #[error("Decode {status}: {source}")]
+    Decode {
+        body: String,
+        status: StatusCode,
+        headers: Box<HeaderMap>,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// The call did not finish within its deadline, including retries. Also
+    /// returned when the loopback callback times out or a device code expires.
+    #[error("DeadlineExceeded {0:?}")]
+    DeadlineExceeded(Duration),
+
+    /// The call was stopped by its `CancellationToken`.
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+#[cfg(feature = "network")]
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Reqwest(Box::new(e))
+    }
+}
+
+impl Error {
+    /// Status code of the response, if one was received.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            #[cfg(feature = "network")]
+            Error::Reqwest(e) => e.status(),
+            Error::Invalid(_) | Error::DeadlineExceeded(_) | Error::Cancelled => None,
+            Error::ClientError(_, status, _) | Error::RetryOver(_, status, _) => Some(*status),
+            Error::Decode { status, .. } => Some(*status),
+        }
+    }
+
+    /// Response headers, if a response was received.
+    pub fn headers(&self) -> Option<&HeaderMap> {
+        match self {
+            Error::ClientError(_, _, headers) | Error::RetryOver(_, _, headers) => Some(headers),
+            Error::Decode { headers, .. } => Some(headers),
+            _ => None,
+        }
+    }
+
+    /// 429 Too Many Requests.
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(StatusCode::TOO_MANY_REQUESTS)
+    }
+
+    /// The same request may succeed later: rate limits, server errors after
+    /// all retries, an exceeded deadline and transport failures such as timeouts.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            #[cfg(feature = "network")]
+            Error::Reqwest(e) => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if e.is_connect() {
+                    return true;
+                }
+                e.is_timeout() || e.is_request()
+            }
+            Error::ClientError(..) => self.is_rate_limited(),
+            Error::RetryOver(..) | Error::DeadlineExceeded(_) => true,
+            Error::Invalid(_) | Error::Decode { .. } | Error::Cancelled => false,
+        }
+    }
+
+    /// The token or grant is no longer valid and the user has to log in again:
+    /// 401, or 400 with `invalid_grant` / `invalid_token` from the token endpoint.
+    pub fn requires_reauthorization(&self) -> bool {
+        match self {
+            Error::ClientError(_, StatusCode::UNAUTHORIZED, _) => true,
+            Error::ClientError(body, StatusCode::BAD_REQUEST, _) => {
+                body.contains("invalid_grant") || body.contains("invalid_token")
+            }
+            _ => false,
+        }
+    }
+
+    /// Delay requested by the server, from `retry-after` in seconds or the
+    /// epoch seconds of `x-rate-limit-reset`.
+    pub fn retry_after(&self) -> Option<Duration> {
+        let headers = self.headers()?;
+        let header = |name| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+        if let Some(seconds) = header(RETRY_AFTER.as_str()) {
+            return Some(Duration::from_secs(seconds));
+        }
+        let reset = header(X_RATE_LIMIT_RESET)?;
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        Some(Duration::from_secs(reset.saturating_sub(now)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_error(body: &str, status: StatusCode, headers: &[(&'static str, &str)]) -> Error {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, value.parse().unwrap());
+        }
+        Error::ClientError(body.to_owned(), status, Box::new(map))
+    }
+
+    #[test]
+    fn test_classification() {
+        let rate_limited =
+            client_error("", StatusCode::TOO_MANY_REQUESTS, &[("retry-after", "30")]);
+        assert!(rate_limited.is_rate_limited());
+        assert!(rate_limited.is_retryable());
+        assert!(!rate_limited.requires_reauthorization());
+        assert_eq!(rate_limited.retry_after(), Some(Duration::from_secs(30)));
+        assert_eq!(rate_limited.status(), Some(StatusCode::TOO_MANY_REQUESTS));
+
+        let reset = (chrono::Utc::now().timestamp() + 60).to_string();
+        let rate_limited = client_error(
+            "",
+            StatusCode::TOO_MANY_REQUESTS,
+            &[("x-rate-limit-reset", &reset)],
+        );
+        assert!(rate_limited.retry_after().unwrap() > Duration::from_secs(50));
+
+        let invalid_grant =
+            client_error(r#"{"error":"invalid_grant"}"#, StatusCode::BAD_REQUEST, &[]);
+        assert!(invalid_grant.requires_reauthorization());
+        assert!(!invalid_grant.is_retryable());
+        assert!(client_error("", StatusCode::UNAUTHORIZED, &[]).requires_reauthorization());
+        assert!(!client_error("", StatusCode::FORBIDDEN, &[]).requires_reauthorization());
+
+        let retry_over = Error::RetryOver(String::new(), StatusCode::BAD_GATEWAY, Box::default());
+        assert!(retry_over.is_retryable());
+        assert_eq!(retry_over.retry_after(), None);
+
+        let decode = Error::Decode {
+            body: r#"{"access_token":"live","expires_in":"7200"}"#.to_owned(),
+            status: StatusCode::OK,
+            headers: Box::default(),
+            source: "invalid type".into(),
+        };
+        assert_eq!(decode.to_string(), "Decode 200 OK: invalid type");
+
+        let invalid = Error::Invalid("state mismatch".to_owned());
+        assert!(!invalid.is_retryable());
+        assert_eq!(invalid.status(), None);
+    }
 }
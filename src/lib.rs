@@ -1,8 +1,31 @@
-use std::time::Duration;
+#[cfg(all(feature = "axum", any(feature = "oauth1a", feature = "oauth2")))]
+pub mod axum;
 
-use reqwest::{RequestBuilder, StatusCode, header::HeaderMap};
+#[cfg(all(feature = "blocking", any(feature = "oauth1a", feature = "oauth2")))]
+mod blocking;
 
 pub mod error;
+#[cfg(feature = "network")]
+mod limits;
+#[cfg(feature = "network")]
+mod recorder;
+#[cfg(feature = "network")]
+mod retry;
+#[cfg(feature = "network")]
+mod runtime;
+pub mod secret;
+
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+
+#[cfg(feature = "middleware")]
+pub mod middleware;
+
+#[cfg(all(feature = "oauth1a", feature = "oauth2"))]
+pub mod migration;
+
+#[cfg(all(feature = "loopback", any(feature = "oauth1a", feature = "oauth2")))]
+mod loopback;
 
 #[cfg(feature = "oauth1a")]
 pub mod oauth1a;
@@ -10,81 +33,51 @@ pub mod oauth1a;
 #[cfg(feature = "oauth2")]
 pub mod oauth2;
 
+#[cfg(feature = "network")]
+pub use limits::CallLimits;
+#[cfg(feature = "network")]
 pub use reqwest;
+pub use secret::Secret;
+#[cfg(feature = "network")]
+pub use tokio_util::sync::CancellationToken;
+
+#[cfg(all(feature = "network", feature = "oauth1a"))]
+pub(crate) use retry::Form;
+#[cfg(all(feature = "network", feature = "oauth2"))]
+pub(crate) use retry::Text;
+#[cfg(feature = "network")]
+pub(crate) use retry::{Json, execute_retry};
 
 use crate::error::Error;
 
-#[allow(dead_code)]
-pub(crate) async fn execute_retry<T>(
-    f: impl Fn() -> RequestBuilder,
-    try_count: usize,
-    retry_duration: Duration,
-) -> Result<(T, StatusCode, HeaderMap), Error>
-where
-    T: serde::de::DeserializeOwned,
-{
-    for i in 0..try_count {
-        let req = f();
-        let res = req.send().await?;
-        let status = res.status();
-        let headers = res.headers().clone();
-        if status.is_success() {
-            let json: T = res.json().await?;
-            return Ok((json, status, headers));
-        } else if status.is_client_error() {
-            let body = res.text().await.unwrap_or_default();
-            return Err(Error::ClientError(body, status, headers));
-        }
-        if i + 1 < try_count {
-            // ジッターとエクスポーネンシャルバックオフを組み合わせる
-            let jitter: u64 = rand::random::<u64>() % retry_duration.as_millis() as u64;
-            let exp_backoff = 2u64.pow(i as u32) * retry_duration.as_millis() as u64;
-            let retry_duration = Duration::from_millis(exp_backoff + jitter);
-            tokio::time::sleep(retry_duration).await;
-        } else {
-            let body = res.text().await.unwrap_or_default();
-            return Err(Error::RetryOver(body, status, headers));
-        }
+pub(crate) fn make_url(base_url: &str, path: &str, prefix_url: &Option<String>) -> String {
+    if let Some(prefix_url) = prefix_url {
+        format!("{}{}", prefix_url, path)
+    } else {
+        format!("{}{}", base_url, path)
     }
-    unreachable!()
 }
 
+/// Validates the `prefix_url` option of the client constructors.
 #[allow(dead_code)]
-pub(crate) async fn execute_retry_body(
-    f: impl Fn() -> RequestBuilder,
-    try_count: usize,
-    retry_duration: Duration,
-) -> Result<(String, StatusCode, HeaderMap), Error> {
-    for i in 0..try_count {
-        let req = f();
-        let res = req.send().await?;
-        let status = res.status();
-        let headers = res.headers().clone();
-        if status.is_success() {
-            let body = res.text().await?;
-            return Ok((body, status, headers));
-        } else if status.is_client_error() {
-            let body = res.text().await.unwrap_or_default();
-            return Err(Error::ClientError(body, status, headers));
-        }
-        if i + 1 < try_count {
-            // ジッターとエクスポーネンシャルバックオフを組み合わせる
-            let jitter: u64 = rand::random::<u64>() % retry_duration.as_millis() as u64;
-            let exp_backoff = 2u64.pow(i as u32) * retry_duration.as_millis() as u64;
-            let retry_duration = Duration::from_millis(exp_backoff + jitter);
-            tokio::time::sleep(retry_duration).await;
-        } else {
-            let body = res.text().await.unwrap_or_default();
-            return Err(Error::RetryOver(body, status, headers));
-        }
+pub(crate) fn validate_prefix_url(prefix_url: &Option<String>) -> Result<(), Error> {
+    if let Some(prefix_url) = prefix_url {
+        validate_url(prefix_url)?;
     }
-    unreachable!()
+    Ok(())
 }
 
-pub(crate) fn make_url(base_url: &str, path: &str, prefix_url: &Option<String>) -> String {
-    if let Some(prefix_url) = prefix_url {
-        format!("{}{}", prefix_url, path)
-    } else {
-        format!("{}{}", base_url, path)
+/// Returns the url when it is an absolute http or https URL.
+#[allow(dead_code)]
+pub(crate) fn validate_url(url: &str) -> Result<String, Error> {
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => {
+            Ok(url.to_string())
+        }
+        Ok(_) => Err(Error::Invalid(format!(
+            "url must be http or https: {}",
+            url
+        ))),
+        Err(e) => Err(Error::Invalid(format!("url {}: {}", url, e))),
     }
 }
@@ -0,0 +1,102 @@
+use std::{future::Future, time::Duration};
+
+#[cfg(feature = "blocking")]
+use std::{cell::RefCell, time::Instant};
+
+use crate::{CancellationToken, error::Error, runtime};
+
+#[cfg(feature = "blocking")]
+thread_local! {
+    static BLOCKING_LIMITS: RefCell<Option<(CallLimits, Instant)>> = const { RefCell::new(None) };
+}
+
+/// Deadline and cancellation for one call of any client.
+///
+/// The limits are given per call instead of being stored on a client, so a
+/// cancelled token or an elapsed deadline never affects later calls. `run`
+/// bounds everything the call awaits: every attempt and backoff, device
+/// polling and the loopback redirect wait. The deadline is measured from the
+/// start of each `run`. Exceeding it returns `Error::DeadlineExceeded`, and a
+/// cancelled token returns `Error::Cancelled`.
+#[derive(Debug, Clone, Default)]
+pub struct CallLimits {
+    deadline: Option<Duration>,
+    cancellation_token: Option<CancellationToken>,
+}
+
+impl CallLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Runs `call`, e.g. `limits.run(client.token(code, verifier))`.
+    pub async fn run<T>(&self, call: impl Future<Output = Result<T, Error>>) -> Result<T, Error> {
+        let call = async {
+            match self.deadline {
+                Some(deadline) => runtime::timeout(deadline, call)
+                    .await
+                    .unwrap_or(Err(Error::DeadlineExceeded(deadline))),
+                None => call.await,
+            }
+        };
+        match self.cancellation_token.as_ref() {
+            Some(token) => token
+                .run_until_cancelled(call)
+                .await
+                .unwrap_or(Err(Error::Cancelled)),
+            None => call.await,
+        }
+    }
+
+    /// Runs a blocking call, e.g. `limits.run_blocking(|| client.token_blocking(code, verifier))`.
+    ///
+    /// The limits are checked before each attempt and backoff on this thread.
+    /// A request already in flight is bounded by the client's timeout.
+    #[cfg(feature = "blocking")]
+    pub fn run_blocking<T>(&self, call: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        struct Restore(Option<(CallLimits, Instant)>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                BLOCKING_LIMITS.set(self.0.take());
+            }
+        }
+
+        let _restore = Restore(BLOCKING_LIMITS.replace(Some((self.clone(), Instant::now()))));
+        call()
+    }
+}
+
+/// Fails when the limits of the enclosing `run_blocking` would be exceeded
+/// after waiting `delay`.
+#[cfg(feature = "blocking")]
+pub(crate) fn check_blocking(delay: Duration) -> Result<(), Error> {
+    BLOCKING_LIMITS.with_borrow(|limits| {
+        let Some((limits, started)) = limits else {
+            return Ok(());
+        };
+        if limits
+            .cancellation_token
+            .as_ref()
+            .is_some_and(|it| it.is_cancelled())
+        {
+            return Err(Error::Cancelled);
+        }
+        match limits.deadline {
+            Some(deadline) if started.elapsed() + delay >= deadline => {
+                Err(Error::DeadlineExceeded(deadline))
+            }
+            _ => Ok(()),
+        }
+    })
+}
@@ -0,0 +1,189 @@
+use std::{collections::HashMap, time::Duration};
+
+use reqwest::Url;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::error::Error;
+
+const MAX_REQUEST_HEAD: usize = 8192;
+// browsers open idle preconnect sockets, which must not hold up the callback
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+const SUCCESS_HTML: &str = "<html><body>Login completed. You can close this window.</body></html>";
+const DENIED_HTML: &str =
+    "<html><body>Login was not completed. You can close this window.</body></html>";
+const NOT_FOUND_HTML: &str = "<html><body>Not Found</body></html>";
+
+/// One-shot HTTP listener on 127.0.0.1 that catches the OAuth redirect.
+pub(crate) struct LoopbackListener {
+    listener: TcpListener,
+    path: String,
+}
+
+impl LoopbackListener {
+    /// Binds the port of `callback_url`, whose host must be `127.0.0.1`.
+    ///
+    /// `localhost` is rejected because the browser may resolve it to `::1`.
+    pub(crate) async fn bind(callback_url: &str) -> Result<Self, Error> {
+        let url = Url::parse(callback_url)
+            .map_err(|e| Error::Invalid(format!("callback url {}: {}", callback_url, e)))?;
+        match url.host_str() {
+            Some("127.0.0.1") => {}
+            Some("localhost") => {
+                return Err(Error::Invalid(format!(
+                    "callback url must use 127.0.0.1 instead of localhost: {}",
+                    callback_url
+                )));
+            }
+            _ => {
+                return Err(Error::Invalid(format!(
+                    "callback url is not loopback: {}",
+                    callback_url
+                )));
+            }
+        }
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| Error::Invalid(format!("callback url has no port: {}", callback_url)))?;
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|e| Error::Invalid(format!("bind 127.0.0.1:{}: {}", port, e)))?;
+        Ok(Self {
+            listener,
+            path: url.path().to_owned(),
+        })
+    }
+
+    /// Waits for a request to the callback path and returns its query parameters.
+    ///
+    /// Returns `Error::DeadlineExceeded` when no callback arrives within `timeout`.
+    pub(crate) async fn wait(self, timeout: Duration) -> Result<HashMap<String, String>, Error> {
+        tokio::time::timeout(timeout, self.accept_callback())
+            .await
+            .map_err(|_| Error::DeadlineExceeded(timeout))?
+    }
+
+    async fn accept_callback(&self) -> Result<HashMap<String, String>, Error> {
+        loop {
+            let (mut stream, _) = self
+                .listener
+                .accept()
+                .await
+                .map_err(|e| Error::Invalid(format!("accept: {}", e)))?;
+            let Ok(Some(url)) =
+                tokio::time::timeout(READ_TIMEOUT, read_request_url(&mut stream)).await
+            else {
+                continue;
+            };
+            if url.path() != self.path {
+                write_response(&mut stream, "404 Not Found", NOT_FOUND_HTML).await;
+                continue;
+            }
+            let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            // error is set by OAuth 2.0 and denied by OAuth 1.0a
+            let body = if params.contains_key("error") || params.contains_key("denied") {
+                DENIED_HTML
+            } else {
+                SUCCESS_HTML
+            };
+            write_response(&mut stream, "200 OK", body).await;
+            return Ok(params);
+        }
+    }
+}
+
+async fn read_request_url(stream: &mut TcpStream) -> Option<Url> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|it| it == b"\r\n\r\n") {
+        if buf.len() > MAX_REQUEST_HEAD {
+            return None;
+        }
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let mut request_line = head.lines().next()?.split(' ');
+    let _method = request_line.next()?;
+    let target = request_line.next()?;
+    Url::parse(&format!("http://127.0.0.1{}", target)).ok()
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn listener() -> (LoopbackListener, String) {
+        let listener = LoopbackListener::bind("http://127.0.0.1:0/callback")
+            .await
+            .unwrap();
+        let addr = listener.listener.local_addr().unwrap();
+        (listener, format!("http://{}/callback", addr))
+    }
+
+    #[tokio::test]
+    async fn test_bind_localhost() {
+        assert!(
+            LoopbackListener::bind("http://localhost:0/callback")
+                .await
+                .is_err()
+        );
+        assert!(
+            LoopbackListener::bind("http://example.com:0/callback")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_connection() {
+        let (listener, callback) = listener().await;
+        let addr = listener.listener.local_addr().unwrap();
+        let wait = tokio::spawn(listener.wait(Duration::from_secs(10)));
+
+        // a preconnect socket that never sends a request
+        let _idle = TcpStream::connect(addr).await.unwrap();
+        let res = reqwest::get(format!("{}?code=abc", callback))
+            .await
+            .unwrap();
+        assert!(res.text().await.unwrap().contains("Login completed"));
+        let params = wait.await.unwrap().unwrap();
+        assert_eq!(params.get("code").unwrap(), "abc");
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let (listener, _) = listener().await;
+        let res = listener.wait(Duration::from_millis(50)).await;
+        assert!(matches!(res, Err(Error::DeadlineExceeded(_))));
+        assert!(res.unwrap_err().is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_denied() {
+        let (listener, callback) = listener().await;
+        let wait = tokio::spawn(listener.wait(Duration::from_secs(10)));
+        let res = reqwest::get(format!("{}?error=access_denied", callback))
+            .await
+            .unwrap();
+        assert!(res.text().await.unwrap().contains("not completed"));
+        let params = wait.await.unwrap().unwrap();
+        assert_eq!(params.get("error").unwrap(), "access_denied");
+    }
+}
@@ -0,0 +1,498 @@
+pub use reqwest_middleware;
+
+#[cfg(feature = "oauth2")]
+pub use self::bearer::{BearerMiddleware, RefreshTokenSource, TokenSource};
+
+#[cfg(feature = "oauth1a")]
+pub use self::oauth1a::OAuth1aMiddleware;
+
+#[cfg(feature = "oauth2")]
+mod bearer {
+    use std::sync::{Arc, RwLock};
+
+    use async_trait::async_trait;
+    use http::Extensions;
+    use reqwest::{
+        Request, Response, StatusCode,
+        header::{AUTHORIZATION, HeaderValue},
+    };
+    use reqwest_middleware::{Middleware, Next};
+    use tokio::sync::Mutex;
+
+    use crate::{
+        Secret,
+        error::Error,
+        oauth2::{OAuth2Client, Scope, TokenResult},
+    };
+
+    /// Source of the access token attached by `BearerMiddleware`.
+    #[async_trait]
+    pub trait TokenSource: Send + Sync + 'static {
+        async fn access_token(&self) -> Result<Secret<String>, Error>;
+
+        /// Called once when `expired` was rejected with 401. Returns the new access token.
+        async fn refresh(&self, expired: &str) -> Result<Secret<String>, Error>;
+    }
+
+    type OnRefresh = Box<dyn Fn(&TokenResult) + Send + Sync>;
+
+    /// `TokenSource` refreshing with the refresh token grant of `OAuth2Client`.
+    ///
+    /// Requests rejected with the same token share a single refresh.
+    pub struct RefreshTokenSource<S: Scope> {
+        client: OAuth2Client<S>,
+        token: RwLock<TokenResult>,
+        refreshing: Mutex<()>,
+        on_refresh: Option<OnRefresh>,
+    }
+
+    impl<S: Scope> RefreshTokenSource<S> {
+        pub fn new(client: impl Into<OAuth2Client<S>>, token: TokenResult) -> Self {
+            Self {
+                client: client.into(),
+                token: RwLock::new(token),
+                refreshing: Mutex::new(()),
+                on_refresh: None,
+            }
+        }
+
+        /// Called with the new token after each refresh, e.g. to persist it.
+        pub fn with_on_refresh(
+            mut self,
+            on_refresh: impl Fn(&TokenResult) + Send + Sync + 'static,
+        ) -> Self {
+            self.on_refresh = Some(Box::new(on_refresh));
+            self
+        }
+
+        pub fn token(&self) -> TokenResult {
+            self.token.read().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl<S: Scope + Send + Sync + 'static> TokenSource for RefreshTokenSource<S> {
+        async fn access_token(&self) -> Result<Secret<String>, Error> {
+            Ok(self.token.read().unwrap().access_token.clone())
+        }
+
+        async fn refresh(&self, expired: &str) -> Result<Secret<String>, Error> {
+            let _refreshing = self.refreshing.lock().await;
+            let refresh_token = {
+                let token = self.token.read().unwrap();
+                // another request has already refreshed
+                if token.access_token.expose() != expired {
+                    return Ok(token.access_token.clone());
+                }
+                token
+                    .refresh_token
+                    .clone()
+                    .ok_or_else(|| Error::Invalid("no refresh token".to_owned()))?
+            };
+            let (mut token, _, _) = self.client.refresh_token(refresh_token.expose()).await?;
+            if token.refresh_token.is_none() {
+                token.refresh_token = Some(refresh_token);
+            }
+            let access_token = token.access_token.clone();
+            *self.token.write().unwrap() = token.clone();
+            if let Some(on_refresh) = self.on_refresh.as_ref() {
+                on_refresh(&token);
+            }
+            Ok(access_token)
+        }
+    }
+
+    fn bearer(access_token: &Secret<String>) -> reqwest_middleware::Result<HeaderValue> {
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", access_token.expose()))
+            .map_err(reqwest_middleware::Error::middleware)?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+
+    /// Attaches `Authorization: Bearer` to every request. On 401 the token is
+    /// refreshed once and the request is replayed if its body can be cloned.
+    pub struct BearerMiddleware<T: TokenSource> {
+        source: Arc<T>,
+    }
+
+    impl<T: TokenSource> BearerMiddleware<T> {
+        pub fn new(source: Arc<T>) -> Self {
+            Self { source }
+        }
+    }
+
+    #[async_trait]
+    impl<T: TokenSource> Middleware for BearerMiddleware<T> {
+        async fn handle(
+            &self,
+            mut req: Request,
+            extensions: &mut Extensions,
+            next: Next<'_>,
+        ) -> reqwest_middleware::Result<Response> {
+            let access_token = self
+                .source
+                .access_token()
+                .await
+                .map_err(reqwest_middleware::Error::middleware)?;
+            req.headers_mut()
+                .insert(AUTHORIZATION, bearer(&access_token)?);
+            let replay = req.try_clone();
+            let res = next.clone().run(req, extensions).await?;
+            if res.status() != StatusCode::UNAUTHORIZED {
+                return Ok(res);
+            }
+            let Some(mut replay) = replay else {
+                return Ok(res);
+            };
+            let access_token = self
+                .source
+                .refresh(access_token.expose())
+                .await
+                .map_err(reqwest_middleware::Error::middleware)?;
+            replay
+                .headers_mut()
+                .insert(AUTHORIZATION, bearer(&access_token)?);
+            next.run(replay, extensions).await
+        }
+    }
+}
+
+#[cfg(feature = "oauth1a")]
+mod oauth1a {
+    use async_trait::async_trait;
+    use http::Extensions;
+    use reqwest::{
+        Request, Response,
+        header::{AUTHORIZATION, CONTENT_TYPE, HeaderValue},
+    };
+    use reqwest_middleware::{Middleware, Next};
+
+    use crate::{Secret, oauth1a::calc_oauth1a::oauth1_authorization_header};
+
+    /// Signs every request with OAuth 1.0a user context.
+    ///
+    /// Query parameters and `application/x-www-form-urlencoded` bodies are
+    /// included in the signature base string.
+    pub struct OAuth1aMiddleware {
+        consumer_key: String,
+        consumer_secret: Secret<String>,
+        access_token: Secret<String>,
+        access_token_secret: Secret<String>,
+    }
+
+    impl OAuth1aMiddleware {
+        pub fn new(
+            consumer_key: &str,
+            consumer_secret: &str,
+            access_token: &str,
+            access_token_secret: &str,
+        ) -> Self {
+            Self {
+                consumer_key: consumer_key.to_string(),
+                consumer_secret: Secret::from(consumer_secret),
+                access_token: Secret::from(access_token),
+                access_token_secret: Secret::from(access_token_secret),
+            }
+        }
+
+        fn authorization(&self, req: &Request) -> String {
+            let mut url = req.url().clone();
+            let mut params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+            url.set_query(None);
+            url.set_fragment(None);
+            let is_form = req
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|it| it.to_str().ok())
+                .is_some_and(|it| it.starts_with("application/x-www-form-urlencoded"));
+            if is_form && let Some(body) = req.body().and_then(|it| it.as_bytes()) {
+                params.extend(form_urlencoded::parse(body).into_owned());
+            }
+            let options: Vec<(&str, &str)> = params
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            oauth1_authorization_header(
+                &self.consumer_key,
+                self.consumer_secret.expose(),
+                self.access_token.expose(),
+                self.access_token_secret.expose(),
+                req.method().as_str(),
+                url.as_str(),
+                &options,
+            )
+        }
+    }
+
+    #[async_trait]
+    impl Middleware for OAuth1aMiddleware {
+        async fn handle(
+            &self,
+            mut req: Request,
+            extensions: &mut Extensions,
+            next: Next<'_>,
+        ) -> reqwest_middleware::Result<Response> {
+            let mut authorization = HeaderValue::from_str(&self.authorization(&req))
+                .map_err(reqwest_middleware::Error::middleware)?;
+            authorization.set_sensitive(true);
+            req.headers_mut().insert(AUTHORIZATION, authorization);
+            next.run(req, extensions).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "oauth2"))]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use axum::{Form, Json, Router, extract::State, http::HeaderMap, routing::post};
+    use reqwest::StatusCode;
+    use reqwest_middleware::ClientBuilder;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        Secret,
+        oauth2::{TokenResult, XClient, XScope},
+    };
+
+    async fn token(
+        State(refreshes): State<Arc<AtomicUsize>>,
+        Form(params): Form<Vec<(String, String)>>,
+    ) -> Json<Value> {
+        assert!(params.contains(&("refresh_token".to_owned(), "refresh".to_owned())));
+        refreshes.fetch_add(1, Ordering::SeqCst);
+        // keeps the refresh in flight while concurrent requests arrive
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Json(json!({
+            "access_token": "new_access",
+            "refresh_token": "new_refresh",
+            "expires_in": 7200,
+            "scope": "tweet.read",
+            "token_type": "bearer",
+        }))
+    }
+
+    async fn me(headers: HeaderMap) -> (StatusCode, &'static str) {
+        match headers.get("Authorization").and_then(|it| it.to_str().ok()) {
+            Some("Bearer new_access") => (StatusCode::OK, "ok"),
+            _ => (StatusCode::UNAUTHORIZED, "unauthorized"),
+        }
+    }
+
+    async fn start_server() -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let prefix_url = format!("http://{}", listener.local_addr().unwrap());
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route("/2/oauth2/token", post(token))
+            .route("/2/users/me", post(me))
+            .with_state(refreshes.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (prefix_url, refreshes)
+    }
+
+    fn client(prefix_url: &str) -> XClient {
+        XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            vec![XScope::TweetRead],
+            1,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+            Some(prefix_url.to_owned()),
+        )
+        .unwrap()
+    }
+
+    fn expired_token() -> TokenResult {
+        TokenResult {
+            access_token: Secret::from("old_access"),
+            refresh_token: Some(Secret::from("refresh")),
+            expires_in: 7200,
+            scope: "tweet.read".to_owned(),
+            token_type: "bearer".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bearer_middleware_refresh() {
+        let (prefix_url, _) = start_server().await;
+        let refreshed = Arc::new(AtomicUsize::new(0));
+        let counter = refreshed.clone();
+        let source = Arc::new(
+            RefreshTokenSource::new(client(&prefix_url), expired_token()).with_on_refresh(
+                move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                },
+            ),
+        );
+        let http = ClientBuilder::new(reqwest::Client::new())
+            .with(BearerMiddleware::new(source.clone()))
+            .build();
+
+        let res = http
+            .post(format!("{}/2/users/me", prefix_url))
+            .body("payload")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            source.token().refresh_token.unwrap().expose(),
+            "new_refresh"
+        );
+
+        let res = http
+            .post(format!("{}/2/users/me", prefix_url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(refreshed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_single_flight() {
+        let (prefix_url, refreshes) = start_server().await;
+        let source = RefreshTokenSource::new(client(&prefix_url), expired_token());
+        let (a, b, c) = tokio::join!(
+            source.refresh("old_access"),
+            source.refresh("old_access"),
+            source.refresh("old_access"),
+        );
+        for access_token in [a, b, c] {
+            assert_eq!(access_token.unwrap().expose(), "new_access");
+        }
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[cfg(all(test, feature = "oauth1a"))]
+mod oauth1a_tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        Router,
+        extract::{RawQuery, State},
+        http::HeaderMap,
+        routing::post,
+    };
+    use base64::prelude::*;
+    use hmac::{Hmac, Mac};
+    use reqwest_middleware::ClientBuilder;
+
+    use super::*;
+    use crate::oauth1a::calc_oauth1a::encode;
+
+    type Received = Arc<Mutex<Option<(String, String, String)>>>;
+
+    async fn statuses(
+        State(received): State<Received>,
+        headers: HeaderMap,
+        RawQuery(query): RawQuery,
+        body: String,
+    ) -> String {
+        let authorization = headers.get("Authorization").unwrap().to_str().unwrap();
+        *received.lock().unwrap() = Some((
+            authorization.to_owned(),
+            query.unwrap_or_default(),
+            body.clone(),
+        ));
+        body
+    }
+
+    /// Signature of RFC 5849 3.4 computed from the received request.
+    fn expected_signature(
+        authorization: &str,
+        url: &str,
+        params: &[(String, String)],
+        key: &str,
+    ) -> String {
+        let mut pairs: Vec<String> = authorization
+            .strip_prefix("OAuth ")
+            .unwrap()
+            .split(", ")
+            .filter(|it| !it.starts_with("oauth_signature="))
+            .map(|it| it.to_owned())
+            .collect();
+        pairs.extend(
+            params
+                .iter()
+                .map(|(key, value)| format!("{}={}", encode(key), encode(value))),
+        );
+        pairs.sort();
+        let base = format!("POST&{}&{}", encode(url), encode(&pairs.join("&")));
+        let mut mac = Hmac::<sha1::Sha1>::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(base.as_bytes());
+        encode(&BASE64_STANDARD.encode(mac.finalize().into_bytes()))
+    }
+
+    #[tokio::test]
+    async fn test_oauth1a_middleware() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let received = Received::default();
+        let app = Router::new()
+            .route("/1.1/statuses/update.json", post(statuses))
+            .with_state(received.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let http = ClientBuilder::new(reqwest::Client::new())
+            .with(OAuth1aMiddleware::new(
+                "key",
+                "secret",
+                "token",
+                "token_secret",
+            ))
+            .build();
+        let res = http
+            .post(format!(
+                "{}/1.1/statuses/update.json?trim_user=true&q=a%20b",
+                url
+            ))
+            .form(&[("status", "hello world!")])
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        assert_eq!(res.text().await.unwrap(), "status=hello+world%21");
+
+        let (authorization, query, body) = received.lock().unwrap().take().unwrap();
+        assert!(authorization.starts_with("OAuth oauth_consumer_key=key, "));
+        assert!(authorization.contains("oauth_token=token"));
+        let signature = authorization
+            .split(", ")
+            .find_map(|it| it.strip_prefix("oauth_signature="))
+            .unwrap();
+        let query: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+        let form: Vec<(String, String)> = form_urlencoded::parse(body.as_bytes())
+            .into_owned()
+            .collect();
+        let params = [query.clone(), form.clone()].concat();
+        let endpoint = format!("{}/1.1/statuses/update.json", url);
+        let key = "secret&token_secret";
+        assert_eq!(
+            signature,
+            expected_signature(&authorization, &endpoint, &params, key)
+        );
+        // dropping either the query or the form params breaks the signature
+        assert_ne!(
+            signature,
+            expected_signature(&authorization, &endpoint, &query, key)
+        );
+        assert_ne!(
+            signature,
+            expected_signature(&authorization, &endpoint, &form, key)
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Secret,
+    error::Error,
+    oauth1a::AccessToken,
+    oauth2::{TokenResult, XClient, XUser, random_state},
+};
+
+/// Pending move of an OAuth 1.0a user to OAuth 2.0, started by
+/// `XClient::start_migration`.
+///
+/// Serializable so it can be kept in the session until the callback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Migration {
+    pub user_id: Option<String>,
+    pub screen_name: String,
+    pub url: String,
+    pub state: String,
+    code_verifier: Secret<String>,
+}
+
+/// OAuth 2.0 token linked to the user of the OAuth 1.0a token.
+#[derive(Debug, Clone)]
+pub struct MigratedToken {
+    pub user_id: String,
+    pub token: TokenResult,
+    pub user: XUser,
+}
+
+impl XClient {
+    /// Authorize URL for the user of `access_token`. Send the user to
+    /// `Migration::url`.
+    ///
+    /// X's OAuth 2.0 authorize endpoint has no login hint parameter, so the URL
+    /// cannot pre-fill the account; `Migration::finish` checks that the user
+    /// signed in with the same one.
+    pub fn start_migration(&self, access_token: &AccessToken) -> Migration {
+        let state = random_state();
+        let (url, code_verifier) = self.authorize_url(&state);
+        Migration {
+            user_id: access_token.user_id.clone(),
+            screen_name: access_token.screen_name.clone(),
+            url,
+            state,
+            code_verifier: Secret::new(code_verifier),
+        }
+    }
+}
+
+impl Migration {
+    /// Exchanges the callback `code` and checks with `XClient::me` that the
+    /// same user authorized. Tokens stored without `user_id` are matched by
+    /// `screen_name`.
+    pub async fn finish(
+        &self,
+        client: &XClient,
+        state: &str,
+        code: &str,
+    ) -> Result<MigratedToken, Error> {
+        if state != self.state {
+            return Err(Error::Invalid("state mismatch".to_owned()));
+        }
+        let (token, _, _) = client.token(code, self.code_verifier.expose()).await?;
+        let (user, _, _) = client.me(&token).await?;
+        let mismatch = match self.user_id.as_deref() {
+            Some(user_id) if user.id != user_id => Some(format!(
+                "user_id {}, got {} ({})",
+                user_id, user.id, user.username
+            )),
+            None if !user.username.eq_ignore_ascii_case(&self.screen_name) => Some(format!(
+                "screen_name {}, got {}",
+                self.screen_name, user.username
+            )),
+            _ => None,
+        };
+        if let Some(mismatch) = mismatch {
+            return Err(Error::Invalid(format!(
+                "migrated user mismatch: expected {}",
+                mismatch
+            )));
+        }
+        Ok(MigratedToken {
+            user_id: user.id.clone(),
+            token,
+            user,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        mock_server::{MockEndpoint, MockServer},
+        oauth1a::OAuth1aClient,
+        oauth2::XScope,
+    };
+
+    fn x_client(server: &MockServer) -> XClient {
+        XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            XScope::all(),
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_migration() {
+        let server = MockServer::start_with_user("42", "alice").await.unwrap();
+        let oauth1a = OAuth1aClient::new_with_options(
+            "key",
+            "secret",
+            "http://localhost/callback",
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap();
+        let request_token = oauth1a.request_token(None).await.unwrap();
+        let access_token = oauth1a
+            .access_token(
+                &request_token.response.oauth_token,
+                request_token.response.oauth_token_secret.expose(),
+                "verifier",
+            )
+            .await
+            .unwrap();
+
+        let client = x_client(&server);
+        let migration = client.start_migration(&access_token);
+        assert_eq!(migration.user_id.as_deref(), Some("42"));
+        let res = migration.finish(&client, "other", "code").await;
+        assert!(matches!(res, Err(Error::Invalid(_))));
+
+        let migrated = migration
+            .finish(&client, &migration.state, "code")
+            .await
+            .unwrap();
+        assert_eq!(migrated.user_id, "42");
+        assert_eq!(migrated.user.username, "alice");
+        assert_eq!(server.requests(MockEndpoint::UsersMe), 1);
+    }
+
+    #[tokio::test]
+    async fn test_migration_user_mismatch() {
+        let server = MockServer::start_with_user("42", "alice").await.unwrap();
+        let access_token = AccessToken {
+            oauth_token: Secret::from("token"),
+            oauth_token_secret: Secret::from("secret"),
+            screen_name: "bob".to_owned(),
+            user_id: None,
+        };
+        let client = x_client(&server);
+        let migration = client.start_migration(&access_token);
+        let res = migration.finish(&client, &migration.state, "code").await;
+        assert!(
+            matches!(res, Err(Error::Invalid(message)) if message.contains("expected screen_name bob"))
+        );
+
+        // matched by user_id when the token has one, even if the screen name changed
+        let access_token = AccessToken {
+            screen_name: "alice".to_owned(),
+            user_id: Some("7".to_owned()),
+            ..access_token
+        };
+        let migration = client.start_migration(&access_token);
+        let res = migration.finish(&client, &migration.state, "code").await;
+        assert!(
+            matches!(res, Err(Error::Invalid(message)) if message.contains("expected user_id 7, got 42"))
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use ::axum::{
+    Form, Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde_json::json;
+use tokio::{net::TcpListener, task::JoinHandle};
+
+use crate::error::Error;
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Endpoint emulated by `MockServer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockEndpoint {
+    /// `/2/oauth2/token`
+    Token,
+    /// `/2/oauth2/revoke`
+    Revoke,
+    /// `/oauth/request_token`
+    RequestToken,
+    /// `/oauth/access_token`
+    AccessToken,
+    /// `/1.1/oauth/invalidate_token`
+    InvalidateToken,
+    /// `/2/users/me`
+    UsersMe,
+    /// `/oauth2/token`
+    AppOnlyToken,
+    /// `/oauth2/invalidate_token`
+    AppOnlyInvalidateToken,
+    /// `/2/oauth2/device_authorization`, not offered by X. Configure it with
+    /// `OAuth2Endpoints::with_device_authorization_url`.
+    DeviceAuthorization,
+}
+
+/// Scripted failure returned instead of a successful response.
+#[derive(Debug, Clone)]
+pub enum MockFailure {
+    /// Status code with an empty body, e.g. 503.
+    Status(StatusCode),
+    /// 429 with a `retry-after` header in seconds.
+    RateLimited { retry_after: u64 },
+    /// 400 with `{"error":"invalid_grant"}`.
+    InvalidGrant,
+    /// 200 with a body that is not valid JSON.
+    MalformedBody,
+    /// 400 with `{"error":"authorization_pending"}` from a device token poll.
+    AuthorizationPending,
+    /// 400 with `{"error":"slow_down"}` from a device token poll.
+    SlowDown,
+    /// 400 with `{"error":"access_denied"}` from a device token poll.
+    AccessDenied,
+}
+
+impl IntoResponse for MockFailure {
+    fn into_response(self) -> Response {
+        match self {
+            MockFailure::Status(status) => status.into_response(),
+            MockFailure::RateLimited { retry_after } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(json!({"title": "Too Many Requests"})),
+            )
+                .into_response(),
+            MockFailure::InvalidGrant => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": "invalid_grant",
+                    "error_description": "Value passed for the token was invalid.",
+                })),
+            )
+                .into_response(),
+            MockFailure::MalformedBody => {
+                (StatusCode::OK, "<html>unexpected</html>").into_response()
+            }
+            MockFailure::AuthorizationPending => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "authorization_pending"})),
+            )
+                .into_response(),
+            MockFailure::SlowDown => {
+                (StatusCode::BAD_REQUEST, Json(json!({"error": "slow_down"}))).into_response()
+            }
+            MockFailure::AccessDenied => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "access_denied"})),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Request received by `MockServer`.
+#[derive(Debug, Clone, Default)]
+pub struct MockRequest {
+    /// `Authorization` header.
+    pub authorization: Option<String>,
+    /// Form body parameters.
+    pub params: HashMap<String, String>,
+}
+
+impl MockRequest {
+    fn new(headers: &HeaderMap, params: HashMap<String, String>) -> Self {
+        Self {
+            authorization: headers
+                .get(header::AUTHORIZATION)
+                .and_then(|it| it.to_str().ok())
+                .map(|it| it.to_owned()),
+            params,
+        }
+    }
+}
+
+struct MockState {
+    user_id: String,
+    screen_name: String,
+    failures: Mutex<HashMap<MockEndpoint, VecDeque<MockFailure>>>,
+    requests: Mutex<HashMap<MockEndpoint, usize>>,
+    last_requests: Mutex<HashMap<MockEndpoint, MockRequest>>,
+}
+
+impl MockState {
+    /// Records the request and pops the next scripted failure.
+    fn hit(&self, endpoint: MockEndpoint, request: MockRequest) -> Option<MockFailure> {
+        *self.requests.lock().unwrap().entry(endpoint).or_default() += 1;
+        self.last_requests.lock().unwrap().insert(endpoint, request);
+        self.failures
+            .lock()
+            .unwrap()
+            .get_mut(&endpoint)
+            .and_then(|it| it.pop_front())
+    }
+
+    fn requests(&self, endpoint: MockEndpoint) -> usize {
+        self.requests
+            .lock()
+            .unwrap()
+            .get(&endpoint)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// In-process HTTP server emulating the X OAuth endpoints.
+///
+/// Pass `url()` as `prefix_url` of `XClient` or `OAuth1aClient`. Every
+/// request succeeds unless a failure has been scripted with `fail`.
+pub struct MockServer {
+    url: String,
+    state: Arc<MockState>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> Result<Self, Error> {
+        Self::start_with_user("1234567890", "mock_user").await
+    }
+
+    /// Starts with the user returned by `/oauth/access_token` and `/2/users/me`.
+    pub async fn start_with_user(user_id: &str, screen_name: &str) -> Result<Self, Error> {
+        let state = Arc::new(MockState {
+            user_id: user_id.to_owned(),
+            screen_name: screen_name.to_owned(),
+            failures: Mutex::new(HashMap::new()),
+            requests: Mutex::new(HashMap::new()),
+            last_requests: Mutex::new(HashMap::new()),
+        });
+        let app = Router::new()
+            .route("/2/oauth2/token", post(token))
+            .route("/2/oauth2/revoke", post(revoke))
+            .route("/oauth/request_token", post(request_token))
+            .route("/oauth/access_token", post(access_token))
+            .route("/1.1/oauth/invalidate_token", post(invalidate_token))
+            .route("/2/users/me", get(users_me))
+            .route("/oauth2/token", post(app_only_token))
+            .route("/oauth2/invalidate_token", post(app_only_invalidate_token))
+            .route("/2/oauth2/device_authorization", post(device_authorization))
+            .with_state(state.clone());
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| Error::Invalid(format!("mock server bind: {}", e)))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| Error::Invalid(format!("mock server addr: {}", e)))?;
+        let handle = tokio::spawn(async move {
+            let _ = ::axum::serve(listener, app).await;
+        });
+        Ok(Self {
+            url: format!("http://{}", addr),
+            state,
+            handle,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Queues `failure` for the next request to `endpoint`.
+    pub fn fail(&self, endpoint: MockEndpoint, failure: MockFailure) {
+        self.fail_times(endpoint, failure, 1);
+    }
+
+    /// Queues `failure` for the next `times` requests to `endpoint`.
+    pub fn fail_times(&self, endpoint: MockEndpoint, failure: MockFailure, times: usize) {
+        let mut failures = self.state.failures.lock().unwrap();
+        let queue = failures.entry(endpoint).or_default();
+        for _ in 0..times {
+            queue.push_back(failure.clone());
+        }
+    }
+
+    /// Number of requests received by `endpoint`, including failed ones.
+    pub fn requests(&self, endpoint: MockEndpoint) -> usize {
+        self.state.requests(endpoint)
+    }
+
+    /// Last request received by `endpoint`.
+    pub fn last_request(&self, endpoint: MockEndpoint) -> Option<MockRequest> {
+        self.state
+            .last_requests
+            .lock()
+            .unwrap()
+            .get(&endpoint)
+            .cloned()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn token(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Form(params): Form<HashMap<String, String>>,
+) -> Response {
+    let count = state.requests(MockEndpoint::Token);
+    let grant_type = params.get("grant_type").cloned();
+    if let Some(failure) = state.hit(MockEndpoint::Token, MockRequest::new(&headers, params)) {
+        return failure.into_response();
+    }
+    match grant_type.as_deref() {
+        Some("authorization_code") | Some("refresh_token") | Some(DEVICE_CODE_GRANT_TYPE) => {
+            Json(json!({
+            "access_token": format!("mock_access_token_{}", count),
+            "refresh_token": format!("mock_refresh_token_{}", count),
+            "expires_in": 7200,
+            "scope": "tweet.read users.read offline.access",
+            "token_type": "bearer",
+            }))
+            .into_response()
+        }
+        _ => MockFailure::InvalidGrant.into_response(),
+    }
+}
+
+async fn device_authorization(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Form(params): Form<HashMap<String, String>>,
+) -> Response {
+    let request = MockRequest::new(&headers, params);
+    if let Some(failure) = state.hit(MockEndpoint::DeviceAuthorization, request) {
+        return failure.into_response();
+    }
+    Json(json!({
+        "device_code": "mock_device_code",
+        "user_code": "MOCK-CODE",
+        "verification_uri": "https://example.com/device",
+        "expires_in": 60,
+        "interval": 0,
+    }))
+    .into_response()
+}
+
+async fn revoke(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Form(params): Form<HashMap<String, String>>,
+) -> Response {
+    if let Some(failure) = state.hit(MockEndpoint::Revoke, MockRequest::new(&headers, params)) {
+        return failure.into_response();
+    }
+    Json(json!({"revoked": true})).into_response()
+}
+
+async fn request_token(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    if let Some(failure) = state.hit(
+        MockEndpoint::RequestToken,
+        MockRequest::new(&headers, HashMap::new()),
+    ) {
+        return failure.into_response();
+    }
+    "oauth_token=mock_request_token&oauth_token_secret=mock_request_token_secret&oauth_callback_confirmed=true"
+        .into_response()
+}
+
+async fn access_token(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    if let Some(failure) = state.hit(
+        MockEndpoint::AccessToken,
+        MockRequest::new(&headers, HashMap::new()),
+    ) {
+        return failure.into_response();
+    }
+    format!(
+        "oauth_token=mock_access_token&oauth_token_secret=mock_access_token_secret&user_id={}&screen_name={}",
+        state.user_id, state.screen_name
+    )
+    .into_response()
+}
+
+async fn invalidate_token(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    if let Some(failure) = state.hit(
+        MockEndpoint::InvalidateToken,
+        MockRequest::new(&headers, HashMap::new()),
+    ) {
+        return failure.into_response();
+    }
+    Json(json!({"access_token": "mock_access_token"})).into_response()
+}
+
+async fn users_me(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let with_email = params
+        .get("user.fields")
+        .is_some_and(|it| it.split(',').any(|field| field == "confirmed_email"));
+    if let Some(failure) = state.hit(MockEndpoint::UsersMe, MockRequest::new(&headers, params)) {
+        return failure.into_response();
+    }
+    let mut user = json!({
+        "id": state.user_id,
+        "username": state.screen_name,
+        "name": state.screen_name,
+    });
+    if with_email {
+        user["confirmed_email"] = json!(format!("{}@example.com", state.screen_name));
+    }
+    Json(json!({ "data": user })).into_response()
+}
+
+async fn app_only_token(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    let count = state.requests(MockEndpoint::AppOnlyToken);
+    if let Some(failure) = state.hit(
+        MockEndpoint::AppOnlyToken,
+        MockRequest::new(&headers, HashMap::new()),
+    ) {
+        return failure.into_response();
+    }
+    Json(json!({
+        "token_type": "bearer",
+        "access_token": format!("mock_bearer_token_{}", count),
+    }))
+    .into_response()
+}
+
+async fn app_only_invalidate_token(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Form(params): Form<HashMap<String, String>>,
+) -> Response {
+    let access_token = params.get("access_token").cloned();
+    let request = MockRequest::new(&headers, params);
+    if let Some(failure) = state.hit(MockEndpoint::AppOnlyInvalidateToken, request) {
+        return failure.into_response();
+    }
+    Json(json!({"access_token": access_token})).into_response()
+}
+
+#[cfg(all(test, feature = "oauth2"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::oauth2::{XClient, XScope};
+
+    fn client(server: &MockServer) -> XClient {
+        XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            XScope::all(),
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_token_retry() {
+        let server = MockServer::start().await.unwrap();
+        server.fail_times(
+            MockEndpoint::Token,
+            MockFailure::Status(StatusCode::SERVICE_UNAVAILABLE),
+            2,
+        );
+        let (token, _, _) = client(&server).token("code", "verifier").await.unwrap();
+        assert_eq!(token.access_token.expose(), "mock_access_token_2");
+        assert_eq!(server.requests(MockEndpoint::Token), 3);
+    }
+
+    #[tokio::test]
+    async fn test_token_retry_over() {
+        let server = MockServer::start().await.unwrap();
+        server.fail_times(
+            MockEndpoint::Token,
+            MockFailure::Status(StatusCode::BAD_GATEWAY),
+            3,
+        );
+        let res = client(&server).token("code", "verifier").await;
+        assert!(matches!(
+            res,
+            Err(Error::RetryOver(_, StatusCode::BAD_GATEWAY, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_invalid_grant() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(MockEndpoint::Token, MockFailure::InvalidGrant);
+        let res = client(&server).refresh_token("refresh").await;
+        assert!(
+            matches!(res, Err(Error::ClientError(body, StatusCode::BAD_REQUEST, _)) if body.contains("invalid_grant"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_malformed_body() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(MockEndpoint::Token, MockFailure::MalformedBody);
+        let res = client(&server).token("code", "verifier").await;
+        assert!(
+            matches!(res, Err(Error::Decode { body, status: StatusCode::OK, .. }) if body == "<html>unexpected</html>")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_rate_limited() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(
+            MockEndpoint::Revoke,
+            MockFailure::RateLimited { retry_after: 1 },
+        );
+        let res = client(&server).revoke_token("access", None).await;
+        assert!(
+            matches!(res, Err(Error::ClientError(_, StatusCode::TOO_MANY_REQUESTS, headers)) if headers.contains_key("retry-after"))
+        );
+        let (body, _, _) = client(&server).revoke_token("access", None).await.unwrap();
+        assert!(body.contains("revoked"));
+    }
+}
+
+#[cfg(all(test, feature = "oauth1a"))]
+mod oauth1a_tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::oauth1a::OAuth1aClient;
+
+    fn client(server: &MockServer) -> OAuth1aClient {
+        OAuth1aClient::new_with_options(
+            "key",
+            "secret",
+            "http://localhost/callback",
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_oauth1a_flow() {
+        let server = MockServer::start_with_user("42", "alice").await.unwrap();
+        server.fail(
+            MockEndpoint::RequestToken,
+            MockFailure::Status(StatusCode::INTERNAL_SERVER_ERROR),
+        );
+        let client = client(&server);
+        let request_token = client.request_token(None).await.unwrap();
+        assert_eq!(request_token.response.oauth_token, "mock_request_token");
+        assert_eq!(server.requests(MockEndpoint::RequestToken), 2);
+        let access_token = client
+            .access_token(
+                &request_token.response.oauth_token,
+                request_token.response.oauth_token_secret.expose(),
+                "verifier",
+            )
+            .await
+            .unwrap();
+        assert_eq!(access_token.screen_name, "alice");
+        assert_eq!(access_token.user_id.as_deref(), Some("42"));
+    }
+
+    #[tokio::test]
+    async fn test_request_token_malformed_body() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(MockEndpoint::RequestToken, MockFailure::MalformedBody);
+        let res = client(&server).request_token(None).await;
+        assert!(
+            matches!(res, Err(Error::Decode { body, status: StatusCode::OK, .. }) if body == "<html>unexpected</html>")
+        );
+
+        server.fail(MockEndpoint::AccessToken, MockFailure::MalformedBody);
+        let res = client(&server)
+            .access_token(
+                "mock_request_token",
+                "mock_request_token_secret",
+                "verifier",
+            )
+            .await;
+        assert!(matches!(res, Err(Error::Decode { .. })));
+    }
+}
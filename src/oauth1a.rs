@@ -1,14 +1,26 @@
-use crate::{error::Error, execute_retry_body, make_url, oauth1a::calc_oauth1a::calc_oauth_header};
+use crate::{
+    Form, Json, Secret,
+    error::Error,
+    execute_retry, make_url,
+    oauth1a::calc_oauth1a::{calc_oauth_header, oauth1_authorization_header},
+    validate_prefix_url, validate_url,
+};
 use reqwest::{StatusCode, header::HeaderMap};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, time::Duration};
 
+#[cfg(feature = "blocking")]
+mod blocking;
 pub mod calc_oauth1a;
+#[cfg(feature = "loopback")]
+mod loopback;
 
 const BASE_URL_PREFIX: &str = "https://api.x.com";
 const REQUEST_TOKEN_URL_POSTFIX: &str = "/oauth/request_token";
 const ACCESS_TOKEN_URL_POSTFIX: &str = "/oauth/access_token";
-const AUTHORIZE_URL: &str = "https://api.x.com/oauth/authorize?oauth_token=";
+const INVALIDATE_TOKEN_URL_POSTFIX: &str = "/1.1/oauth/invalidate_token";
+const AUTHORIZE_URL_POSTFIX: &str = "/oauth/authorize";
+const AUTHENTICATE_URL_POSTFIX: &str = "/oauth/authenticate";
 
 pub enum XAuthAccessType {
     Read,
@@ -24,6 +36,99 @@ impl XAuthAccessType {
     }
 }
 
+/// Endpoint the user is sent to after `request_token`.
+///
+/// `Authenticate` is "Sign in with X": returning users who already approved
+/// the app are redirected back without being asked to consent again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuthorizeEndpoint {
+    #[default]
+    Authorize,
+    Authenticate,
+}
+
+/// Endpoints of the OAuth 1.0a flow. Every URL is validated when it is set.
+#[derive(Debug, Clone)]
+pub struct OAuth1aEndpoints {
+    request_token_url: String,
+    authorize_url: String,
+    authenticate_url: String,
+    access_token_url: String,
+    invalidate_token_url: String,
+}
+
+impl OAuth1aEndpoints {
+    /// X endpoints.
+    pub fn x() -> Self {
+        Self::x_with_base_url(&None)
+    }
+
+    /// X endpoints. `prefix_url` replaces the host of every endpoint, e.g. for a mock server.
+    pub(crate) fn x_with_prefix_url(prefix_url: &Option<String>) -> Result<Self, Error> {
+        validate_prefix_url(prefix_url)?;
+        Ok(Self::x_with_base_url(prefix_url))
+    }
+
+    fn x_with_base_url(prefix_url: &Option<String>) -> Self {
+        Self {
+            request_token_url: make_url(BASE_URL_PREFIX, REQUEST_TOKEN_URL_POSTFIX, prefix_url),
+            authorize_url: make_url(BASE_URL_PREFIX, AUTHORIZE_URL_POSTFIX, prefix_url),
+            authenticate_url: make_url(BASE_URL_PREFIX, AUTHENTICATE_URL_POSTFIX, prefix_url),
+            access_token_url: make_url(BASE_URL_PREFIX, ACCESS_TOKEN_URL_POSTFIX, prefix_url),
+            invalidate_token_url: make_url(
+                BASE_URL_PREFIX,
+                INVALIDATE_TOKEN_URL_POSTFIX,
+                prefix_url,
+            ),
+        }
+    }
+
+    pub fn with_request_token_url(mut self, request_token_url: &str) -> Result<Self, Error> {
+        self.request_token_url = validate_url(request_token_url)?;
+        Ok(self)
+    }
+
+    pub fn with_authorize_url(mut self, authorize_url: &str) -> Result<Self, Error> {
+        self.authorize_url = validate_url(authorize_url)?;
+        Ok(self)
+    }
+
+    pub fn with_authenticate_url(mut self, authenticate_url: &str) -> Result<Self, Error> {
+        self.authenticate_url = validate_url(authenticate_url)?;
+        Ok(self)
+    }
+
+    pub fn with_access_token_url(mut self, access_token_url: &str) -> Result<Self, Error> {
+        self.access_token_url = validate_url(access_token_url)?;
+        Ok(self)
+    }
+
+    pub fn with_invalidate_token_url(mut self, invalidate_token_url: &str) -> Result<Self, Error> {
+        self.invalidate_token_url = validate_url(invalidate_token_url)?;
+        Ok(self)
+    }
+
+    pub fn request_token_url(&self) -> &str {
+        &self.request_token_url
+    }
+
+    pub fn authorize_url(&self) -> &str {
+        &self.authorize_url
+    }
+
+    pub fn authenticate_url(&self) -> &str {
+        &self.authenticate_url
+    }
+
+    pub fn access_token_url(&self) -> &str {
+        &self.access_token_url
+    }
+
+    pub fn invalidate_token_url(&self) -> &str {
+        &self.invalidate_token_url
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RequestToken {
     pub response: RequestTokenResponse,
@@ -33,40 +138,48 @@ pub struct RequestToken {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RequestTokenResponse {
     pub oauth_token: String,
-    pub oauth_token_secret: String,
+    pub oauth_token_secret: Secret<String>,
     pub oauth_callback_confirmed: String,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AccessToken {
-    pub oauth_token: String,
-    pub oauth_token_secret: String,
+    pub oauth_token: Secret<String>,
+    pub oauth_token_secret: Secret<String>,
     pub screen_name: String,
+    /// `None` for tokens stored before `user_id` was recorded.
+    pub user_id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct InvalidateToken {
+    pub access_token: Secret<String>,
 }
 
 pub struct OAuth1aClient {
     consumer_key: String,
-    consumer_secret: String,
+    consumer_secret: Secret<String>,
     callback_url: String,
     try_count: usize,
     retry_duration: Duration,
     timeout: Duration,
-    prefix_url: Option<String>,
+    endpoints: OAuth1aEndpoints,
 }
 
 impl OAuth1aClient {
     pub fn new(consumer_key: &str, consumer_secret: &str, callback_url: &str) -> Self {
-        Self::new_with_options(
+        Self::new_with_endpoints(
             consumer_key,
             consumer_secret,
             callback_url,
+            OAuth1aEndpoints::x(),
             3,
             Duration::from_millis(100),
             Duration::from_secs(10),
-            None,
         )
     }
 
+    /// Returns `Error::Invalid` when `prefix_url` is not an http or https URL.
     #[allow(clippy::too_many_arguments)]
     pub fn new_with_options(
         consumer_key: &str,
@@ -76,42 +189,162 @@ impl OAuth1aClient {
         retry_duration: Duration,
         timeout: Duration,
         prefix_url: Option<String>,
+    ) -> Result<Self, Error> {
+        Ok(Self::new_with_endpoints(
+            consumer_key,
+            consumer_secret,
+            callback_url,
+            OAuth1aEndpoints::x_with_prefix_url(&prefix_url)?,
+            try_count,
+            retry_duration,
+            timeout,
+        ))
+    }
+
+    /// Client with every endpoint configured, e.g. for staging or a mock server.
+    pub fn new_with_endpoints(
+        consumer_key: &str,
+        consumer_secret: &str,
+        callback_url: &str,
+        endpoints: OAuth1aEndpoints,
+        try_count: usize,
+        retry_duration: Duration,
+        timeout: Duration,
     ) -> Self {
         OAuth1aClient {
             consumer_key: consumer_key.to_string(),
-            consumer_secret: consumer_secret.to_string(),
+            consumer_secret: Secret::from(consumer_secret),
             callback_url: callback_url.to_string(),
             try_count,
             retry_duration,
             timeout,
-            prefix_url,
+            endpoints,
         }
     }
 
+    pub fn endpoints(&self) -> &OAuth1aEndpoints {
+        &self.endpoints
+    }
+
+    #[cfg(feature = "axum")]
+    pub(crate) fn callback_url(&self) -> &str {
+        &self.callback_url
+    }
+
     pub async fn request_token(
         &self,
         x_auth_access_type: Option<XAuthAccessType>,
     ) -> Result<RequestToken, Error> {
+        self.request_token_with_options(
+            x_auth_access_type,
+            AuthorizeEndpoint::Authorize,
+            false,
+            None,
+        )
+        .await
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(consumer_key = %self.consumer_key)))]
+    pub async fn request_token_with_options(
+        &self,
+        x_auth_access_type: Option<XAuthAccessType>,
+        authorize_endpoint: AuthorizeEndpoint,
+        force_login: bool,
+        screen_name: Option<&str>,
+    ) -> Result<RequestToken, Error> {
+        let url = self.endpoints.request_token_url.as_str();
+        let signed = self.request_token_header(x_auth_access_type.as_ref());
+        let client = reqwest::Client::new();
+        let (response, _, _): (RequestTokenResponse, StatusCode, HeaderMap) = execute_retry(
+            "request_token",
+            Form,
+            || {
+                client
+                    .post(url)
+                    .header("Authorization", &signed)
+                    .timeout(self.timeout)
+            },
+            self.try_count,
+            self.retry_duration,
+        )
+        .await?;
+        Ok(self.request_token_result(response, authorize_endpoint, force_login, screen_name))
+    }
+
+    fn request_token_header(&self, x_auth_access_type: Option<&XAuthAccessType>) -> String {
         let mut header_options = vec![("oauth_callback", self.callback_url.as_str())];
-        if let Some(x_auth_access_type) = x_auth_access_type.as_ref() {
+        if let Some(x_auth_access_type) = x_auth_access_type {
             header_options.push(("x_auth_access_type", x_auth_access_type.as_str()));
         }
-        let url = make_url(BASE_URL_PREFIX, REQUEST_TOKEN_URL_POSTFIX, &self.prefix_url);
         let signed = calc_oauth_header(
-            &format!("{}&", self.consumer_secret),
+            &format!("{}&", self.consumer_secret.expose()),
             &self.consumer_key,
             &header_options,
             "POST",
-            &url,
+            &self.endpoints.request_token_url,
             &vec![],
         );
-        let signed = format!("OAuth {}", signed);
-        println!("signed: {}", signed);
+        format!("OAuth {}", signed)
+    }
+
+    fn request_token_result(
+        &self,
+        response: RequestTokenResponse,
+        authorize_endpoint: AuthorizeEndpoint,
+        force_login: bool,
+        screen_name: Option<&str>,
+    ) -> RequestToken {
+        let url = self.authorize_url(
+            &response.oauth_token,
+            authorize_endpoint,
+            force_login,
+            screen_name,
+        );
+        RequestToken { response, url }
+    }
+
+    pub fn authorize_url(
+        &self,
+        oauth_token: &str,
+        authorize_endpoint: AuthorizeEndpoint,
+        force_login: bool,
+        screen_name: Option<&str>,
+    ) -> String {
+        let endpoint = match authorize_endpoint {
+            AuthorizeEndpoint::Authorize => &self.endpoints.authorize_url,
+            AuthorizeEndpoint::Authenticate => &self.endpoints.authenticate_url,
+        };
+        // every endpoint is validated when it is set
+        let mut url = url::Url::parse(endpoint).expect("validated authorize url");
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("oauth_token", oauth_token);
+            if force_login {
+                query.append_pair("force_login", "true");
+            }
+            if let Some(screen_name) = screen_name {
+                query.append_pair("screen_name", screen_name);
+            }
+        }
+        url.to_string()
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(consumer_key = %self.consumer_key)))]
+    pub async fn access_token(
+        &self,
+        oauth_token: &str,
+        oauth_token_secret: &str,
+        oauth_verifier: &str,
+    ) -> Result<AccessToken, Error> {
+        let url = self.endpoints.access_token_url.as_str();
+        let signed = self.access_token_header(oauth_token, oauth_token_secret, oauth_verifier);
         let client = reqwest::Client::new();
-        let (res, _, _): (String, StatusCode, HeaderMap) = execute_retry_body(
+        let (access_token, _, _): (AccessToken, StatusCode, HeaderMap) = execute_retry(
+            "access_token",
+            Form,
             || {
                 client
-                    .post(&url)
+                    .post(url)
                     .header("Authorization", &signed)
                     .timeout(self.timeout)
             },
@@ -119,45 +352,52 @@ impl OAuth1aClient {
             self.retry_duration,
         )
         .await?;
-        let map = parse_oauth_body(res);
-        let oauth_token = map.get("oauth_token").unwrap().to_string();
-        let oauth_token_secret = map.get("oauth_token_secret").unwrap().to_string();
-        let oauth_callback_confirmed = map.get("oauth_callback_confirmed").unwrap().to_string();
-        let url = format!("{}{}", AUTHORIZE_URL, oauth_token);
-        Ok(RequestToken {
-            response: RequestTokenResponse {
-                oauth_token,
-                oauth_token_secret,
-                oauth_callback_confirmed,
-            },
-            url,
-        })
+        Ok(access_token)
     }
 
-    pub async fn access_token(
+    fn access_token_header(
         &self,
         oauth_token: &str,
         oauth_token_secret: &str,
         oauth_verifier: &str,
-    ) -> Result<AccessToken, Error> {
-        let url = make_url(BASE_URL_PREFIX, ACCESS_TOKEN_URL_POSTFIX, &self.prefix_url);
+    ) -> String {
         let signed = calc_oauth_header(
-            &format!("{}&{}", self.consumer_secret, oauth_token_secret),
+            &format!("{}&{}", self.consumer_secret.expose(), oauth_token_secret),
             &self.consumer_key,
             &vec![
                 ("oauth_token", oauth_token),
                 ("oauth_verifier", oauth_verifier),
             ],
             "POST",
-            &url,
+            &self.endpoints.access_token_url,
+            &vec![],
+        );
+        format!("OAuth {}", signed)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(consumer_key = %self.consumer_key)))]
+    pub async fn invalidate_token(
+        &self,
+        access_token: &str,
+        access_token_secret: &str,
+    ) -> Result<InvalidateToken, Error> {
+        let url = self.endpoints.invalidate_token_url.as_str();
+        let signed = oauth1_authorization_header(
+            &self.consumer_key,
+            self.consumer_secret.expose(),
+            access_token,
+            access_token_secret,
+            "POST",
+            url,
             &vec![],
         );
-        let signed = format!("OAuth {}", signed);
         let client = reqwest::Client::new();
-        let (res, _, _): (String, StatusCode, HeaderMap) = execute_retry_body(
+        let (res, _, _): (InvalidateToken, StatusCode, HeaderMap) = execute_retry(
+            "invalidate_token",
+            Json,
             || {
                 client
-                    .post(&url)
+                    .post(url)
                     .header("Authorization", &signed)
                     .timeout(self.timeout)
             },
@@ -165,15 +405,7 @@ impl OAuth1aClient {
             self.retry_duration,
         )
         .await?;
-        let map = parse_oauth_body(res);
-        let oauth_token = map.get("oauth_token").unwrap().to_string();
-        let oauth_token_secret = map.get("oauth_token_secret").unwrap().to_string();
-        let screen_name = map.get("screen_name").unwrap().to_string();
-        Ok(AccessToken {
-            oauth_token,
-            oauth_token_secret,
-            screen_name,
-        })
+        Ok(res)
     }
 }
 
@@ -188,3 +420,141 @@ pub fn parse_oauth_body(body: String) -> HashMap<String, String> {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "mock-server")]
+    use crate::mock_server::{MockEndpoint, MockFailure, MockServer};
+
+    #[test]
+    fn test_authorize_url() {
+        let client = OAuth1aClient::new("key", "secret", "http://localhost/callback");
+        assert_eq!(
+            client.authorize_url("abc", AuthorizeEndpoint::Authorize, false, None),
+            "https://api.x.com/oauth/authorize?oauth_token=abc"
+        );
+
+        let client = OAuth1aClient::new_with_options(
+            "key",
+            "secret",
+            "http://localhost/callback",
+            3,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+            Some("http://localhost:8080".to_owned()),
+        )
+        .unwrap();
+        assert_eq!(
+            client.authorize_url("abc", AuthorizeEndpoint::Authenticate, true, Some("a b")),
+            "http://localhost:8080/oauth/authenticate?oauth_token=abc&force_login=true&screen_name=a+b"
+        );
+        assert!(
+            OAuth1aClient::new_with_options(
+                "key",
+                "secret",
+                "http://localhost/callback",
+                3,
+                Duration::from_millis(100),
+                Duration::from_secs(10),
+                Some("localhost:8080".to_owned()),
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_access_token_without_user_id() {
+        let token: AccessToken = serde_urlencoded::from_str(
+            "oauth_token=token&oauth_token_secret=secret&screen_name=alice",
+        )
+        .unwrap();
+        assert_eq!(token.screen_name, "alice");
+        assert_eq!(token.user_id, None);
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let endpoints = OAuth1aEndpoints::x()
+            .with_authorize_url("http://localhost:8080/oauth/authorize")
+            .unwrap()
+            .with_access_token_url("http://localhost:8081/oauth/access_token")
+            .unwrap();
+        assert_eq!(
+            endpoints.request_token_url(),
+            "https://api.x.com/oauth/request_token"
+        );
+        assert_eq!(
+            endpoints.access_token_url(),
+            "http://localhost:8081/oauth/access_token"
+        );
+        assert!(
+            OAuth1aEndpoints::x()
+                .with_request_token_url("not a url")
+                .is_err()
+        );
+
+        let client = OAuth1aClient::new_with_endpoints(
+            "key",
+            "secret",
+            "http://localhost/callback",
+            endpoints,
+            3,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+        );
+        assert_eq!(
+            client.authorize_url("abc", AuthorizeEndpoint::Authorize, false, None),
+            "http://localhost:8080/oauth/authorize?oauth_token=abc"
+        );
+    }
+
+    #[cfg(feature = "mock-server")]
+    fn mock_client(server: &MockServer) -> OAuth1aClient {
+        OAuth1aClient::new_with_options(
+            "key",
+            "secret",
+            "http://localhost/callback",
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap()
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_invalidate_token() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(
+            MockEndpoint::InvalidateToken,
+            MockFailure::Status(StatusCode::SERVICE_UNAVAILABLE),
+        );
+        let res = mock_client(&server)
+            .invalidate_token("mock_access_token", "mock_access_token_secret")
+            .await
+            .unwrap();
+        assert_eq!(res.access_token.expose(), "mock_access_token");
+        assert_eq!(server.requests(MockEndpoint::InvalidateToken), 2);
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_invalidate_token_unauthorized() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(
+            MockEndpoint::InvalidateToken,
+            MockFailure::Status(StatusCode::UNAUTHORIZED),
+        );
+        let res = mock_client(&server)
+            .invalidate_token("expired", "expired_secret")
+            .await;
+        assert!(matches!(
+            res,
+            Err(Error::ClientError(_, StatusCode::UNAUTHORIZED, _))
+        ));
+        assert!(res.unwrap_err().requires_reauthorization());
+        assert_eq!(server.requests(MockEndpoint::InvalidateToken), 1);
+    }
+}
@@ -0,0 +1,107 @@
+use reqwest::{StatusCode, header::HeaderMap};
+use serde::de::DeserializeOwned;
+
+use crate::{Form, blocking::execute_retry, error::Error};
+
+use super::{AccessToken, AuthorizeEndpoint, OAuth1aClient, RequestToken, XAuthAccessType};
+
+impl OAuth1aClient {
+    /// Blocking `request_token`. Must not be called from within an async runtime.
+    pub fn request_token_blocking(
+        &self,
+        x_auth_access_type: Option<XAuthAccessType>,
+    ) -> Result<RequestToken, Error> {
+        self.request_token_with_options_blocking(
+            x_auth_access_type,
+            AuthorizeEndpoint::Authorize,
+            false,
+            None,
+        )
+    }
+
+    /// Blocking `request_token_with_options`.
+    pub fn request_token_with_options_blocking(
+        &self,
+        x_auth_access_type: Option<XAuthAccessType>,
+        authorize_endpoint: AuthorizeEndpoint,
+        force_login: bool,
+        screen_name: Option<&str>,
+    ) -> Result<RequestToken, Error> {
+        let signed = self.request_token_header(x_auth_access_type.as_ref());
+        let response =
+            self.post_blocking("request_token", &self.endpoints.request_token_url, &signed)?;
+        Ok(self.request_token_result(response, authorize_endpoint, force_login, screen_name))
+    }
+
+    /// Blocking `access_token`. Must not be called from within an async runtime.
+    pub fn access_token_blocking(
+        &self,
+        oauth_token: &str,
+        oauth_token_secret: &str,
+        oauth_verifier: &str,
+    ) -> Result<AccessToken, Error> {
+        let signed = self.access_token_header(oauth_token, oauth_token_secret, oauth_verifier);
+        self.post_blocking("access_token", &self.endpoints.access_token_url, &signed)
+    }
+
+    fn post_blocking<T: DeserializeOwned>(
+        &self,
+        endpoint: &'static str,
+        url: &str,
+        signed: &str,
+    ) -> Result<T, Error> {
+        let client = reqwest::blocking::Client::new();
+        let (res, _, _): (T, StatusCode, HeaderMap) = execute_retry(
+            endpoint,
+            Form,
+            || {
+                client
+                    .post(url)
+                    .header("Authorization", signed)
+                    .timeout(self.timeout)
+            },
+            self.try_count,
+            self.retry_duration,
+        )?;
+        Ok(res)
+    }
+}
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        mock_server::{MockEndpoint, MockServer},
+        oauth1a::OAuth1aClient,
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_oauth1a_blocking() {
+        let server = MockServer::start_with_user("42", "alice").await.unwrap();
+        let client = OAuth1aClient::new_with_options(
+            "key",
+            "secret",
+            "http://localhost/callback",
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap();
+        let access_token = tokio::task::spawn_blocking(move || {
+            let request_token = client.request_token_blocking(None).unwrap();
+            client
+                .access_token_blocking(
+                    &request_token.response.oauth_token,
+                    request_token.response.oauth_token_secret.expose(),
+                    "verifier",
+                )
+                .unwrap()
+        })
+        .await
+        .unwrap();
+        assert_eq!(access_token.screen_name, "alice");
+        assert_eq!(server.requests(MockEndpoint::AccessToken), 1);
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use crate::{error::Error, loopback::LoopbackListener};
+
+use super::{AccessToken, OAuth1aClient, XAuthAccessType};
+
+impl OAuth1aClient {
+    /// Runs the three-legged flow in one call for desktop and CLI apps.
+    ///
+    /// Listens on the loopback port of `callback_url`, gets a request token,
+    /// passes the authorize URL to `open_url`, waits up to `timeout` for the
+    /// redirect and exchanges the verifier with `access_token`. The request
+    /// token secret never leaves this function. The host of `callback_url`
+    /// must be `127.0.0.1`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(consumer_key = %self.consumer_key)))]
+    pub async fn login_interactive(
+        &self,
+        x_auth_access_type: Option<XAuthAccessType>,
+        timeout: Duration,
+        open_url: impl FnOnce(&str),
+    ) -> Result<AccessToken, Error> {
+        let listener = LoopbackListener::bind(&self.callback_url).await?;
+        let request_token = self.request_token(x_auth_access_type).await?;
+        open_url(&request_token.url);
+
+        let params = listener.wait(timeout).await?;
+        if params.contains_key("denied") {
+            return Err(Error::Invalid("authorization denied".to_owned()));
+        }
+        if params.get("oauth_token") != Some(&request_token.response.oauth_token) {
+            return Err(Error::Invalid("oauth_token mismatch".to_owned()));
+        }
+        let Some(oauth_verifier) = params.get("oauth_verifier") else {
+            return Err(Error::Invalid("oauth_verifier is missing".to_owned()));
+        };
+        self.access_token(
+            &request_token.response.oauth_token,
+            request_token.response.oauth_token_secret.expose(),
+            oauth_verifier,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, http::HeaderMap, routing::post};
+
+    use super::*;
+
+    async fn request_token() -> &'static str {
+        "oauth_token=request&oauth_token_secret=request_secret&oauth_callback_confirmed=true"
+    }
+
+    async fn access_token(headers: HeaderMap) -> &'static str {
+        let authorization = headers.get("Authorization").unwrap().to_str().unwrap();
+        assert!(authorization.contains("oauth_token=request"));
+        assert!(authorization.contains("oauth_verifier=verifier"));
+        "oauth_token=access&oauth_token_secret=access_secret&user_id=1&screen_name=user"
+    }
+
+    #[tokio::test]
+    async fn test_login_interactive() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let prefix_url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route("/oauth/request_token", post(request_token))
+            .route("/oauth/access_token", post(access_token));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let callback_url = format!("http://127.0.0.1:{}/oauth", port);
+        let client = OAuth1aClient::new_with_options(
+            "key",
+            "secret",
+            &callback_url,
+            1,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+            Some(prefix_url),
+        )
+        .unwrap();
+
+        let access_token = client
+            .login_interactive(None, Duration::from_secs(10), |url| {
+                assert!(url.ends_with("/oauth/authorize?oauth_token=request"));
+                let callback = format!(
+                    "{}?oauth_token=request&oauth_verifier=verifier",
+                    callback_url
+                );
+                tokio::spawn(async move {
+                    let res = reqwest::get(callback).await.unwrap();
+                    assert!(res.status().is_success());
+                });
+            })
+            .await
+            .unwrap();
+        assert_eq!(access_token.oauth_token.expose(), "access");
+        assert_eq!(access_token.screen_name, "user");
+    }
+}
@@ -2,21 +2,54 @@ use base64::prelude::*;
 use std::time::Duration;
 
 use query_string_builder::QueryString;
-use reqwest::{StatusCode, header::HeaderMap};
+#[cfg(feature = "network")]
+use reqwest::{RequestBuilder, StatusCode, header::HeaderMap};
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 
-use crate::{error::Error, execute_retry, make_url};
+#[cfg(feature = "network")]
+use crate::{Json, Text, execute_retry};
+use crate::{Secret, error::Error, make_url, validate_prefix_url, validate_url};
+
+#[cfg(feature = "network")]
+pub mod app_only;
+#[cfg(feature = "blocking")]
+mod blocking;
+#[cfg(feature = "network")]
+pub mod device;
+#[cfg(feature = "loopback")]
+mod loopback;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenResult {
-    pub access_token: String,
-    pub refresh_token: String,
+    pub access_token: Secret<String>,
+    /// Only issued when `offline.access` was granted.
+    #[serde(default)]
+    pub refresh_token: Option<Secret<String>>,
+    #[serde(default)]
     pub expires_in: u64,
+    #[serde(default)]
     pub scope: String,
     pub token_type: String,
 }
 
+/// User who authorized the token, returned by `XClient::me`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct XUser {
+    pub id: String,
+    pub username: String,
+    pub name: String,
+    /// Only present from `XClient::me_with_email` when `users.email` was granted.
+    #[serde(default, rename = "confirmed_email")]
+    pub email: Option<String>,
+}
+
+#[cfg(feature = "network")]
+#[derive(Deserialize)]
+struct XUserResponse {
+    data: XUser,
+}
+
 enum ResponseType {
     Code,
     #[allow(unused)]
@@ -47,7 +80,9 @@ impl std::fmt::Display for CodeChallengeMethod {
     }
 }
 
-pub(crate) struct PkceS256 {
+/// PKCE verifier and its S256 challenge. Needs no network, so it also builds
+/// for `wasm32-unknown-unknown` with the `wasm-js` feature.
+pub struct PkceS256 {
     pub code_challenge: String,
     pub code_verifier: String,
 }
@@ -96,11 +131,116 @@ fn authorize_url(
     format!("{}{}", url, qs)
 }
 
+/// Random value for the `state` parameter.
+#[cfg(any(feature = "axum", feature = "loopback", feature = "oauth1a"))]
+pub(crate) fn random_state() -> String {
+    let random_bytes: Vec<u8> = (0..16).map(|_| rand::random::<u8>()).collect();
+    BASE64_URL_SAFE_NO_PAD.encode(&random_bytes)
+}
+
+/// How the client authenticates itself at the token endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClientAuthMethod {
+    /// HTTP Basic authentication with client_id and client_secret.
+    #[default]
+    ClientSecretBasic,
+    /// client_id and client_secret in the form body.
+    ClientSecretPost,
+    /// Public client (native app or SPA) without a secret. Only client_id is sent in the form body.
+    None,
+}
+
+/// Request builders of reqwest's async and blocking clients.
+#[cfg(feature = "network")]
+pub(crate) trait BasicAuth {
+    fn with_basic_auth(self, username: &str, password: &str) -> Self;
+}
+
+#[cfg(feature = "network")]
+impl BasicAuth for RequestBuilder {
+    fn with_basic_auth(self, username: &str, password: &str) -> Self {
+        self.basic_auth(username, Some(password))
+    }
+}
+
+#[cfg(all(feature = "network", feature = "blocking"))]
+impl BasicAuth for reqwest::blocking::RequestBuilder {
+    fn with_basic_auth(self, username: &str, password: &str) -> Self {
+        self.basic_auth(username, Some(password))
+    }
+}
+
+#[cfg(feature = "network")]
+pub(crate) fn apply_client_auth<R: BasicAuth>(
+    req: R,
+    client_id: &str,
+    client_secret: &str,
+    client_auth_method: ClientAuthMethod,
+) -> R {
+    match client_auth_method {
+        ClientAuthMethod::ClientSecretBasic => req.with_basic_auth(client_id, client_secret),
+        ClientAuthMethod::ClientSecretPost | ClientAuthMethod::None => req,
+    }
+}
+
+/// `params` with client_secret appended for `ClientSecretPost`.
+#[cfg(feature = "network")]
+pub(crate) fn client_auth_params<'a>(
+    params: &[(&'a str, &'a str)],
+    client_secret: &'a str,
+    client_auth_method: ClientAuthMethod,
+) -> Vec<(&'a str, &'a str)> {
+    let mut params = params.to_vec();
+    if client_auth_method == ClientAuthMethod::ClientSecretPost {
+        params.push(("client_secret", client_secret));
+    }
+    params
+}
+
+#[cfg(feature = "network")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn token_request<T>(
+    endpoint: &'static str,
+    url: &str,
+    client_id: &str,
+    client_secret: &str,
+    client_auth_method: ClientAuthMethod,
+    params: &[(&str, &str)],
+    timeout: Duration,
+    try_count: usize,
+    retry_duration: Duration,
+) -> Result<(T, StatusCode, HeaderMap), Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let params = client_auth_params(params, client_secret, client_auth_method);
+
+    let client = reqwest::Client::new();
+
+    execute_retry(
+        endpoint,
+        Json,
+        || {
+            apply_client_auth(
+                client.post(url).form(&params).timeout(timeout),
+                client_id,
+                client_secret,
+                client_auth_method,
+            )
+        },
+        try_count,
+        retry_duration,
+    )
+    .await
+}
+
+#[cfg(feature = "network")]
 #[allow(clippy::too_many_arguments)]
 pub(crate) async fn token(
     url: &str,
     client_id: &str,
     client_secret: &str,
+    client_auth_method: ClientAuthMethod,
     redirect_uri: &str,
     code: &str,
     code_verifier: &str,
@@ -116,23 +256,22 @@ pub(crate) async fn token(
         ("client_id", client_id),
         ("code_verifier", code_verifier),
     ];
-
-    let client = reqwest::Client::new();
-
-    execute_retry(
-        || {
-            client
-                .post(url)
-                .form(&params)
-                .basic_auth(client_id, Some(client_secret))
-                .timeout(timeout)
-        },
+    token_request(
+        "token",
+        url,
+        client_id,
+        client_secret,
+        client_auth_method,
+        &params,
+        timeout,
         try_count,
         retry_duration,
     )
     .await
 }
 
+#[cfg(feature = "network")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %client_id)))]
 pub async fn refresh_token(
     client_id: &str,
     client_secret: &str,
@@ -142,29 +281,40 @@ pub async fn refresh_token(
     retry_duration: Duration,
     prefix_url: Option<String>,
 ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+    validate_prefix_url(&prefix_url)?;
     let url = &make_url(URL_POSTFIX, X_TOKEN_URL_PREFIX, &prefix_url);
     let params = [
         ("grant_type", "refresh_token"),
         ("refresh_token", refresh_token),
         ("client_id", client_id),
     ];
-
-    let client = reqwest::Client::new();
-
-    execute_retry(
-        || {
-            client
-                .post(url)
-                .form(&params)
-                .basic_auth(client_id, Some(client_secret))
-                .timeout(timeout)
-        },
+    token_request(
+        "refresh_token",
+        url,
+        client_id,
+        client_secret,
+        ClientAuthMethod::ClientSecretBasic,
+        &params,
+        timeout,
         try_count,
         retry_duration,
     )
     .await
 }
 
+/// A scope that can be requested from an OAuth 2.0 provider.
+pub trait Scope: std::fmt::Display {}
+
+impl Scope for String {}
+
+pub fn scopes_to_string<S: Scope>(scopes: &[S]) -> String {
+    scopes
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
 pub enum XScope {
     TweetRead,
     TweetWrite,
@@ -219,14 +369,12 @@ impl XScope {
     }
 
     pub fn scopes_to_string(scopes: &[XScope]) -> String {
-        scopes
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>()
-            .join(" ")
+        scopes_to_string(scopes)
     }
 }
 
+impl Scope for XScope {}
+
 impl std::fmt::Display for XScope {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -261,66 +409,197 @@ pub const X_AUTHORIZE_URL: &str = "https://x.com/i/oauth2/authorize";
 const URL_POSTFIX: &str = "https://api.x.com";
 pub const X_TOKEN_URL_PREFIX: &str = "/2/oauth2/token";
 
-pub struct XClient {
+pub const X_REVOKE_URL_PREFIX: &str = "/2/oauth2/revoke";
+
+pub const X_USERS_ME_URL_PREFIX: &str = "/2/users/me";
+
+/// Endpoints of an OAuth 2.0 provider. Every URL is validated when it is set.
+#[derive(Debug, Clone)]
+pub struct OAuth2Endpoints {
+    authorize_url: String,
+    token_url: String,
+    revoke_url: Option<String>,
+    device_authorization_url: Option<String>,
+    users_me_url: Option<String>,
+}
+
+impl OAuth2Endpoints {
+    pub fn new(authorize_url: &str, token_url: &str) -> Result<Self, Error> {
+        Ok(Self {
+            authorize_url: validate_url(authorize_url)?,
+            token_url: validate_url(token_url)?,
+            revoke_url: None,
+            device_authorization_url: None,
+            users_me_url: None,
+        })
+    }
+
+    /// X endpoints.
+    pub fn x() -> Self {
+        Self::x_with_base_url(&None)
+    }
+
+    /// X endpoints. `prefix_url` replaces the token host, e.g. for a mock server.
+    pub(crate) fn x_with_prefix_url(prefix_url: &Option<String>) -> Result<Self, Error> {
+        validate_prefix_url(prefix_url)?;
+        Ok(Self::x_with_base_url(prefix_url))
+    }
+
+    fn x_with_base_url(prefix_url: &Option<String>) -> Self {
+        Self {
+            authorize_url: X_AUTHORIZE_URL.to_string(),
+            token_url: make_url(URL_POSTFIX, X_TOKEN_URL_PREFIX, prefix_url),
+            revoke_url: Some(make_url(URL_POSTFIX, X_REVOKE_URL_PREFIX, prefix_url)),
+            device_authorization_url: None,
+            users_me_url: Some(make_url(URL_POSTFIX, X_USERS_ME_URL_PREFIX, prefix_url)),
+        }
+    }
+
+    pub fn with_authorize_url(mut self, authorize_url: &str) -> Result<Self, Error> {
+        self.authorize_url = validate_url(authorize_url)?;
+        Ok(self)
+    }
+
+    pub fn with_token_url(mut self, token_url: &str) -> Result<Self, Error> {
+        self.token_url = validate_url(token_url)?;
+        Ok(self)
+    }
+
+    pub fn with_revoke_url(mut self, revoke_url: &str) -> Result<Self, Error> {
+        self.revoke_url = Some(validate_url(revoke_url)?);
+        Ok(self)
+    }
+
+    pub fn with_device_authorization_url(
+        mut self,
+        device_authorization_url: &str,
+    ) -> Result<Self, Error> {
+        self.device_authorization_url = Some(validate_url(device_authorization_url)?);
+        Ok(self)
+    }
+
+    pub fn with_users_me_url(mut self, users_me_url: &str) -> Result<Self, Error> {
+        self.users_me_url = Some(validate_url(users_me_url)?);
+        Ok(self)
+    }
+
+    pub fn authorize_url(&self) -> &str {
+        &self.authorize_url
+    }
+
+    pub fn token_url(&self) -> &str {
+        &self.token_url
+    }
+
+    pub fn revoke_url(&self) -> Option<&str> {
+        self.revoke_url.as_deref()
+    }
+
+    pub fn device_authorization_url(&self) -> Option<&str> {
+        self.device_authorization_url.as_deref()
+    }
+
+    pub fn users_me_url(&self) -> Option<&str> {
+        self.users_me_url.as_deref()
+    }
+}
+
+/// OAuth 2.0 Authorization Code Flow with PKCE for any provider.
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+pub struct OAuth2Client<S: Scope> {
     client_id: String,
-    client_secret: String,
+    client_secret: Secret<String>,
     redirect_uri: String,
-    scopes: Vec<XScope>,
+    scopes: Vec<S>,
+    endpoints: OAuth2Endpoints,
+    client_auth_method: ClientAuthMethod,
     try_count: usize,
     retry_duration: Duration,
     timeout: Duration,
-    prefix_url: Option<String>,
 }
 
-impl XClient {
+impl<S: Scope> OAuth2Client<S> {
     pub fn new(
         client_id: &str,
         client_secret: &str,
         redirect_uri: &str,
-        scopes: Vec<XScope>,
+        scopes: Vec<S>,
+        endpoints: OAuth2Endpoints,
     ) -> Self {
-        Self::new_with_token_options(
+        Self::new_with_options(
             client_id,
             client_secret,
             redirect_uri,
             scopes,
+            endpoints,
+            ClientAuthMethod::ClientSecretBasic,
+            3,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+        )
+    }
+
+    pub fn new_public(
+        client_id: &str,
+        redirect_uri: &str,
+        scopes: Vec<S>,
+        endpoints: OAuth2Endpoints,
+    ) -> Self {
+        Self::new_with_options(
+            client_id,
+            "",
+            redirect_uri,
+            scopes,
+            endpoints,
+            ClientAuthMethod::None,
             3,
             Duration::from_millis(100),
             Duration::from_secs(10),
-            None,
         )
     }
 
     #[allow(clippy::too_many_arguments)]
-    pub fn new_with_token_options(
+    pub fn new_with_options(
         client_id: &str,
         client_secret: &str,
         redirect_uri: &str,
-        scopes: Vec<XScope>,
+        scopes: Vec<S>,
+        endpoints: OAuth2Endpoints,
+        client_auth_method: ClientAuthMethod,
         try_count: usize,
         retry_duration: Duration,
         timeout: Duration,
-        prefix_url: Option<String>,
     ) -> Self {
         Self {
             client_id: client_id.to_string(),
-            client_secret: client_secret.to_string(),
+            client_secret: Secret::from(client_secret),
             redirect_uri: redirect_uri.to_string(),
             scopes,
+            endpoints,
+            client_auth_method,
             try_count,
             retry_duration,
             timeout,
-            prefix_url,
         }
     }
 
+    pub fn endpoints(&self) -> &OAuth2Endpoints {
+        &self.endpoints
+    }
+
+    #[cfg(feature = "axum")]
+    pub(crate) fn redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.client_id)))]
     pub fn authorize_url(&self, state: &str) -> (String, String) {
         let pkce = PkceS256::new();
 
-        let scopes_str = XScope::scopes_to_string(&self.scopes);
+        let scopes_str = scopes_to_string(&self.scopes);
         (
             authorize_url(
-                X_AUTHORIZE_URL,
+                &self.endpoints.authorize_url,
                 ResponseType::Code,
                 &self.client_id,
                 &self.redirect_uri,
@@ -332,16 +611,21 @@ impl XClient {
             pkce.code_verifier,
         )
     }
+}
 
+#[cfg(feature = "network")]
+impl<S: Scope> OAuth2Client<S> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.client_id)))]
     pub async fn token(
         &self,
         code: &str,
         code_verifier: &str,
     ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
-        let (token_json, status_code, headers) = token(
-            &make_url(URL_POSTFIX, X_TOKEN_URL_PREFIX, &self.prefix_url),
+        token(
+            &self.endpoints.token_url,
             &self.client_id,
-            &self.client_secret,
+            self.client_secret.expose(),
+            self.client_auth_method,
             &self.redirect_uri,
             code,
             code_verifier,
@@ -350,21 +634,306 @@ impl XClient {
             self.try_count,
             self.retry_duration,
         )
+        .await
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.client_id)))]
+    pub async fn refresh_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        let params = [
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", self.client_id.as_str()),
+        ];
+        self.token_request("refresh_token", &self.endpoints.token_url, &params)
+            .await
+    }
+
+    /// Revokes an access or refresh token. Returns the raw response body.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.client_id)))]
+    pub async fn revoke_token(
+        &self,
+        token: &str,
+        token_type_hint: Option<&str>,
+    ) -> Result<(String, StatusCode, HeaderMap), Error> {
+        let Some(url) = self.endpoints.revoke_url.as_deref() else {
+            return Err(Error::Invalid("revoke_url is not configured".to_owned()));
+        };
+        let mut params = vec![("token", token), ("client_id", self.client_id.as_str())];
+        if let Some(token_type_hint) = token_type_hint {
+            params.push(("token_type_hint", token_type_hint));
+        }
+        let params = client_auth_params(
+            &params,
+            self.client_secret.expose(),
+            self.client_auth_method,
+        );
+
+        let client = reqwest::Client::new();
+
+        execute_retry(
+            "revoke",
+            Text,
+            || {
+                apply_client_auth(
+                    client.post(url).form(&params).timeout(self.timeout),
+                    &self.client_id,
+                    self.client_secret.expose(),
+                    self.client_auth_method,
+                )
+            },
+            self.try_count,
+            self.retry_duration,
+        )
+        .await
+    }
+
+    pub(crate) async fn token_request<T>(
+        &self,
+        endpoint: &'static str,
+        url: &str,
+        params: &[(&str, &str)],
+    ) -> Result<(T, StatusCode, HeaderMap), Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        token_request(
+            endpoint,
+            url,
+            &self.client_id,
+            self.client_secret.expose(),
+            self.client_auth_method,
+            params,
+            self.timeout,
+            self.try_count,
+            self.retry_duration,
+        )
+        .await
+    }
+}
+
+/// `OAuth2Client` preset for X.
+pub struct XClient {
+    inner: OAuth2Client<XScope>,
+}
+
+impl XClient {
+    pub fn new(
+        client_id: &str,
+        client_secret: &str,
+        redirect_uri: &str,
+        scopes: Vec<XScope>,
+    ) -> Self {
+        Self::new_with_endpoints(
+            client_id,
+            client_secret,
+            redirect_uri,
+            scopes,
+            OAuth2Endpoints::x(),
+            3,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+        )
+    }
+
+    /// Returns `Error::Invalid` when `prefix_url` is not an http or https URL.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_token_options(
+        client_id: &str,
+        client_secret: &str,
+        redirect_uri: &str,
+        scopes: Vec<XScope>,
+        try_count: usize,
+        retry_duration: Duration,
+        timeout: Duration,
+        prefix_url: Option<String>,
+    ) -> Result<Self, Error> {
+        Ok(Self::new_with_endpoints(
+            client_id,
+            client_secret,
+            redirect_uri,
+            scopes,
+            OAuth2Endpoints::x_with_prefix_url(&prefix_url)?,
+            try_count,
+            retry_duration,
+            timeout,
+        ))
+    }
+
+    /// Client with every endpoint configured, e.g. for staging or a mock server.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_endpoints(
+        client_id: &str,
+        client_secret: &str,
+        redirect_uri: &str,
+        scopes: Vec<XScope>,
+        endpoints: OAuth2Endpoints,
+        try_count: usize,
+        retry_duration: Duration,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            inner: OAuth2Client::new_with_options(
+                client_id,
+                client_secret,
+                redirect_uri,
+                scopes,
+                endpoints,
+                ClientAuthMethod::ClientSecretBasic,
+                try_count,
+                retry_duration,
+                timeout,
+            ),
+        }
+    }
+
+    /// Public client without a secret, e.g. a native app. Token and refresh
+    /// requests are sent without Basic authentication.
+    pub fn new_public(client_id: &str, redirect_uri: &str, scopes: Vec<XScope>) -> Self {
+        Self {
+            inner: OAuth2Client::new_public(client_id, redirect_uri, scopes, OAuth2Endpoints::x()),
+        }
+    }
+
+    /// Returns `Error::Invalid` when `prefix_url` is not an http or https URL.
+    pub fn new_public_with_token_options(
+        client_id: &str,
+        redirect_uri: &str,
+        scopes: Vec<XScope>,
+        try_count: usize,
+        retry_duration: Duration,
+        timeout: Duration,
+        prefix_url: Option<String>,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            inner: OAuth2Client::new_with_options(
+                client_id,
+                "",
+                redirect_uri,
+                scopes,
+                OAuth2Endpoints::x_with_prefix_url(&prefix_url)?,
+                ClientAuthMethod::None,
+                try_count,
+                retry_duration,
+                timeout,
+            ),
+        })
+    }
+
+    /// The generic client this preset wraps.
+    pub fn inner(&self) -> &OAuth2Client<XScope> {
+        &self.inner
+    }
+
+    pub fn endpoints(&self) -> &OAuth2Endpoints {
+        self.inner.endpoints()
+    }
+
+    pub fn authorize_url(&self, state: &str) -> (String, String) {
+        self.inner.authorize_url(state)
+    }
+}
+
+#[cfg(feature = "network")]
+impl XClient {
+    pub async fn token(
+        &self,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        self.inner.token(code, code_verifier).await
+    }
+
+    pub async fn refresh_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        self.inner.refresh_token(refresh_token).await
+    }
+
+    pub async fn revoke_token(
+        &self,
+        token: &str,
+        token_type_hint: Option<&str>,
+    ) -> Result<(String, StatusCode, HeaderMap), Error> {
+        self.inner.revoke_token(token, token_type_hint).await
+    }
+
+    /// Fetches the user who authorized `token` from `users_me_url`.
+    ///
+    /// Returns `Error::Invalid` when the endpoints have no `users_me_url`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.inner.client_id)))]
+    pub async fn me(&self, token: &TokenResult) -> Result<(XUser, StatusCode, HeaderMap), Error> {
+        self.users_me(token, "id,name,username").await
+    }
+
+    /// `me` with `XUser::email` filled in. Requires the `users.email` scope.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.inner.client_id)))]
+    pub async fn me_with_email(
+        &self,
+        token: &TokenResult,
+    ) -> Result<(XUser, StatusCode, HeaderMap), Error> {
+        self.users_me(token, "id,name,username,confirmed_email")
+            .await
+    }
+
+    async fn users_me(
+        &self,
+        token: &TokenResult,
+        user_fields: &str,
+    ) -> Result<(XUser, StatusCode, HeaderMap), Error> {
+        let Some(url) = self.inner.endpoints.users_me_url.as_deref() else {
+            return Err(Error::Invalid("users_me_url is not configured".to_owned()));
+        };
+        let params = [("user.fields", user_fields)];
+
+        let client = reqwest::Client::new();
+
+        let (res, status_code, headers): (XUserResponse, StatusCode, HeaderMap) = execute_retry(
+            "users_me",
+            Json,
+            || {
+                client
+                    .get(url)
+                    .query(&params)
+                    .bearer_auth(token.access_token.expose())
+                    .timeout(self.inner.timeout)
+            },
+            self.inner.try_count,
+            self.inner.retry_duration,
+        )
         .await?;
-        Ok((token_json, status_code, headers))
+        Ok((res.data, status_code, headers))
+    }
+}
+
+impl From<XClient> for OAuth2Client<XScope> {
+    fn from(client: XClient) -> Self {
+        client.inner
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "mock-server")]
+    use crate::{
+        CallLimits,
+        mock_server::{MockEndpoint, MockFailure, MockServer},
+    };
+
+    fn env_or(key: &str, default: &str) -> String {
+        std::env::var(key).unwrap_or_else(|_| default.to_owned())
+    }
 
     // CLIENT_ID=xxx CLIENT_SECRET=xxx REDIRECT_URL=http://localhost:8000/callback cargo test test_x_authorize -- --nocapture
     #[tokio::test]
     async fn test_x_authorize() {
-        let client_id = std::env::var("CLIENT_ID").unwrap();
-        let client_secret = std::env::var("CLIENT_SECRET").unwrap();
-        let redirect_url = std::env::var("REDIRECT_URL").unwrap();
+        let client_id = env_or("CLIENT_ID", "client_id");
+        let client_secret = env_or("CLIENT_SECRET", "client_secret");
+        let redirect_url = env_or("REDIRECT_URL", "http://localhost:8000/callback");
         let state = "test_state";
         let x_client = XClient::new(&client_id, &client_secret, &redirect_url, XScope::all());
         let (auth_url, code_verifier) = x_client.authorize_url(state);
@@ -375,8 +944,8 @@ mod tests {
     // CLIENT_ID=xxx cargo test -- --nocapture
     #[tokio::test]
     async fn test_authorize() {
-        let client_id = std::env::var("CLIENT_ID").unwrap();
-        let redirect_url = std::env::var("REDIRECT_URL").unwrap();
+        let client_id = env_or("CLIENT_ID", "client_id");
+        let redirect_url = env_or("REDIRECT_URL", "http://localhost:8000/callback");
         let state = "test_state";
         let scopes = XScope::scopes_to_string(&XScope::all());
         let code_challenge = "test_code_challenge";
@@ -386,10 +955,272 @@ mod tests {
             &client_id,
             &redirect_url,
             &scopes,
-            &state,
-            &code_challenge,
+            state,
+            code_challenge,
             CodeChallengeMethod::Plain,
         );
         println!("res: {}", res);
     }
+
+    #[test]
+    fn test_generic_authorize_url() {
+        let client = OAuth2Client::new(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            vec!["openid".to_owned(), "profile".to_owned()],
+            OAuth2Endpoints::new("https://example.com/authorize", "https://example.com/token")
+                .unwrap(),
+        );
+        let (url, code_verifier) = client.authorize_url("state");
+        assert!(
+            url.starts_with("https://example.com/authorize?response_type=code&client_id=client")
+        );
+        assert!(url.contains("scope=openid%20profile"));
+        assert!(!code_verifier.is_empty());
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let endpoints = OAuth2Endpoints::x()
+            .with_authorize_url("http://localhost:8080/i/oauth2/authorize")
+            .unwrap()
+            .with_token_url("http://localhost:8081/2/oauth2/token")
+            .unwrap();
+        assert_eq!(
+            endpoints.authorize_url(),
+            "http://localhost:8080/i/oauth2/authorize"
+        );
+        assert_eq!(
+            endpoints.token_url(),
+            "http://localhost:8081/2/oauth2/token"
+        );
+        assert_eq!(
+            endpoints.revoke_url(),
+            Some("https://api.x.com/2/oauth2/revoke")
+        );
+        assert_eq!(
+            endpoints.users_me_url(),
+            Some("https://api.x.com/2/users/me")
+        );
+        assert!(
+            OAuth2Endpoints::x()
+                .with_users_me_url("localhost/2/users/me")
+                .is_err()
+        );
+        assert!(
+            OAuth2Endpoints::x()
+                .with_revoke_url("localhost/revoke")
+                .is_err()
+        );
+        assert!(
+            OAuth2Endpoints::new("https://example.com/authorize", "ftp://example.com").is_err()
+        );
+        assert!(
+            XClient::new_with_token_options(
+                "client",
+                "secret",
+                "http://localhost/callback",
+                XScope::all(),
+                3,
+                Duration::from_millis(100),
+                Duration::from_secs(10),
+                Some("localhost:8080".to_owned()),
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "mock-server")]
+    fn mock_client(server: &MockServer) -> XClient {
+        XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            XScope::all(),
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap()
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_client_secret_basic() {
+        let server = MockServer::start().await.unwrap();
+        mock_client(&server)
+            .token("code", "verifier")
+            .await
+            .unwrap();
+        let request = server.last_request(MockEndpoint::Token).unwrap();
+        assert!(request.authorization.unwrap().starts_with("Basic "));
+        assert!(!request.params.contains_key("client_secret"));
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_client_auth_none() {
+        let server = MockServer::start().await.unwrap();
+        let client = XClient::new_public_with_token_options(
+            "client",
+            "http://localhost/callback",
+            XScope::all(),
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap();
+        client.token("code", "verifier").await.unwrap();
+        let request = server.last_request(MockEndpoint::Token).unwrap();
+        assert_eq!(request.authorization, None);
+        assert_eq!(request.params.get("client_id").unwrap(), "client");
+        assert!(!request.params.contains_key("client_secret"));
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_client_secret_post() {
+        let server = MockServer::start().await.unwrap();
+        let client = OAuth2Client::new_with_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            vec!["tweet.read".to_owned()],
+            OAuth2Endpoints::new(
+                &format!("{}/authorize", server.url()),
+                &format!("{}/2/oauth2/token", server.url()),
+            )
+            .unwrap()
+            .with_revoke_url(&format!("{}/2/oauth2/revoke", server.url()))
+            .unwrap(),
+            ClientAuthMethod::ClientSecretPost,
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+        );
+        client.token("code", "verifier").await.unwrap();
+        let request = server.last_request(MockEndpoint::Token).unwrap();
+        assert_eq!(request.authorization, None);
+        assert_eq!(request.params.get("client_id").unwrap(), "client");
+        assert_eq!(request.params.get("client_secret").unwrap(), "secret");
+
+        client.revoke_token("access", None).await.unwrap();
+        let request = server.last_request(MockEndpoint::Revoke).unwrap();
+        assert_eq!(request.authorization, None);
+        assert_eq!(request.params.get("client_secret").unwrap(), "secret");
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_token_deadline() {
+        let server = MockServer::start().await.unwrap();
+        server.fail_times(
+            MockEndpoint::Token,
+            MockFailure::Status(StatusCode::SERVICE_UNAVAILABLE),
+            3,
+        );
+        let client = XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            XScope::all(),
+            3,
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap();
+        let res = CallLimits::new()
+            .with_deadline(Duration::from_millis(200))
+            .run(client.token("code", "verifier"))
+            .await;
+        assert!(matches!(res, Err(Error::DeadlineExceeded(_))));
+        assert_eq!(server.requests(MockEndpoint::Token), 1);
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_token_cancelled() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(
+            MockEndpoint::Token,
+            MockFailure::Status(StatusCode::SERVICE_UNAVAILABLE),
+        );
+        let token = crate::CancellationToken::new();
+        let client = XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            XScope::all(),
+            3,
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap();
+        let limits = CallLimits::new().with_cancellation_token(token.clone());
+        let cancel = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            token.cancel();
+        });
+        let res = limits.run(client.token("code", "verifier")).await;
+        assert!(matches!(res, Err(Error::Cancelled)));
+        cancel.await.unwrap();
+
+        // the cancelled token only applies to the call it was given to
+        let (token, _, _) = client.token("code", "verifier").await.unwrap();
+        assert_eq!(token.access_token.expose(), "mock_access_token_1");
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_me() {
+        let server = MockServer::start_with_user("42", "alice").await.unwrap();
+        server.fail(
+            MockEndpoint::UsersMe,
+            MockFailure::Status(StatusCode::SERVICE_UNAVAILABLE),
+        );
+        let client = mock_client(&server);
+        let (token, _, _) = client.token("code", "verifier").await.unwrap();
+        let (user, _, _) = client.me(&token).await.unwrap();
+        assert_eq!(user.id, "42");
+        assert_eq!(user.username, "alice");
+        assert_eq!(user.email, None);
+        assert_eq!(server.requests(MockEndpoint::UsersMe), 2);
+        let request = server.last_request(MockEndpoint::UsersMe).unwrap();
+        assert_eq!(
+            request.params.get("user.fields").unwrap(),
+            "id,name,username"
+        );
+
+        let (user, _, _) = client.me_with_email(&token).await.unwrap();
+        assert_eq!(user.email.as_deref(), Some("alice@example.com"));
+    }
+
+    #[cfg(feature = "mock-server")]
+    #[tokio::test]
+    async fn test_me_without_users_me_url() {
+        let server = MockServer::start().await.unwrap();
+        let client = XClient::new_with_endpoints(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            XScope::all(),
+            OAuth2Endpoints::new(
+                &format!("{}/authorize", server.url()),
+                &format!("{}/2/oauth2/token", server.url()),
+            )
+            .unwrap(),
+            1,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+        );
+        let (token, _, _) = client.token("code", "verifier").await.unwrap();
+        let res = client.me(&token).await;
+        assert!(matches!(res, Err(Error::Invalid(_))));
+        assert_eq!(server.requests(MockEndpoint::UsersMe), 0);
+    }
 }
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use reqwest::{StatusCode, header::HeaderMap};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{Json, Secret, error::Error, execute_retry, make_url, validate_prefix_url};
+
+use super::URL_POSTFIX;
+
+pub const APP_ONLY_TOKEN_URL_PREFIX: &str = "/oauth2/token";
+pub const APP_ONLY_INVALIDATE_TOKEN_URL_PREFIX: &str = "/oauth2/invalidate_token";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BearerTokenResult {
+    pub token_type: String,
+    pub access_token: Secret<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvalidateTokenResult {
+    pub access_token: Secret<String>,
+}
+
+/// App-only authentication (client credentials grant) with consumer key and secret.
+///
+/// The bearer token does not expire until it is invalidated, so the first
+/// token obtained is cached and reused by `bearer_token`. Concurrent callers
+/// share a single request while the cache is empty.
+pub struct AppOnlyClient {
+    consumer_key: String,
+    consumer_secret: Secret<String>,
+    try_count: usize,
+    retry_duration: Duration,
+    timeout: Duration,
+    prefix_url: Option<String>,
+    cache: Mutex<Option<BearerTokenResult>>,
+}
+
+impl AppOnlyClient {
+    pub fn new(consumer_key: &str, consumer_secret: &str) -> Self {
+        Self {
+            consumer_key: consumer_key.to_string(),
+            consumer_secret: Secret::from(consumer_secret),
+            try_count: 3,
+            retry_duration: Duration::from_millis(100),
+            timeout: Duration::from_secs(10),
+            prefix_url: None,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Returns `Error::Invalid` when `prefix_url` is not an http or https URL.
+    pub fn new_with_options(
+        consumer_key: &str,
+        consumer_secret: &str,
+        try_count: usize,
+        retry_duration: Duration,
+        timeout: Duration,
+        prefix_url: Option<String>,
+    ) -> Result<Self, Error> {
+        validate_prefix_url(&prefix_url)?;
+        Ok(Self {
+            try_count,
+            retry_duration,
+            timeout,
+            prefix_url,
+            ..Self::new(consumer_key, consumer_secret)
+        })
+    }
+
+    /// Returns the cached bearer token, requesting a new one if none is cached.
+    pub async fn bearer_token(&self) -> Result<BearerTokenResult, Error> {
+        // held across the request so that concurrent callers wait for one token
+        let mut cache = self.cache.lock().await;
+        if let Some(cached) = cache.as_ref() {
+            return Ok(cached.clone());
+        }
+        let (token, _, _) = self.request_token().await?;
+        *cache = Some(token.clone());
+        Ok(token)
+    }
+
+    /// Always requests a bearer token from the token endpoint and caches it.
+    pub async fn token(&self) -> Result<(BearerTokenResult, StatusCode, HeaderMap), Error> {
+        let mut cache = self.cache.lock().await;
+        let res = self.request_token().await?;
+        *cache = Some(res.0.clone());
+        Ok(res)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(consumer_key = %self.consumer_key)))]
+    async fn request_token(&self) -> Result<(BearerTokenResult, StatusCode, HeaderMap), Error> {
+        let url = make_url(URL_POSTFIX, APP_ONLY_TOKEN_URL_PREFIX, &self.prefix_url);
+        let params = [("grant_type", "client_credentials")];
+
+        let client = reqwest::Client::new();
+
+        execute_retry(
+            "app_only_token",
+            Json,
+            || {
+                client
+                    .post(&url)
+                    .form(&params)
+                    .basic_auth(&self.consumer_key, Some(self.consumer_secret.expose()))
+                    .timeout(self.timeout)
+            },
+            self.try_count,
+            self.retry_duration,
+        )
+        .await
+    }
+
+    /// Invalidates the cached bearer token and clears the cache.
+    ///
+    /// Returns `Error::Invalid` when there is no cached token.
+    pub async fn invalidate_token(
+        &self,
+    ) -> Result<(InvalidateTokenResult, StatusCode, HeaderMap), Error> {
+        let Some(cached) = self.cache.lock().await.clone() else {
+            return Err(Error::Invalid("no cached bearer token".to_owned()));
+        };
+        self.invalidate(cached.access_token.expose()).await
+    }
+
+    /// Invalidates the given bearer token. The cache is cleared when it holds the same token.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(consumer_key = %self.consumer_key)))]
+    pub async fn invalidate(
+        &self,
+        access_token: &str,
+    ) -> Result<(InvalidateTokenResult, StatusCode, HeaderMap), Error> {
+        let url = make_url(
+            URL_POSTFIX,
+            APP_ONLY_INVALIDATE_TOKEN_URL_PREFIX,
+            &self.prefix_url,
+        );
+        let params = [("access_token", access_token)];
+
+        let client = reqwest::Client::new();
+
+        let res = execute_retry(
+            "app_only_invalidate",
+            Json,
+            || {
+                client
+                    .post(&url)
+                    .form(&params)
+                    .basic_auth(&self.consumer_key, Some(self.consumer_secret.expose()))
+                    .timeout(self.timeout)
+            },
+            self.try_count,
+            self.retry_duration,
+        )
+        .await?;
+        let mut cache = self.cache.lock().await;
+        if cache
+            .as_ref()
+            .is_some_and(|cached| cached.access_token.expose() == access_token)
+        {
+            *cache = None;
+        }
+        Ok(res)
+    }
+
+    pub async fn clear_cache(&self) {
+        *self.cache.lock().await = None;
+    }
+}
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockEndpoint, MockServer};
+
+    fn client(server: &MockServer) -> AppOnlyClient {
+        AppOnlyClient::new_with_options(
+            "key",
+            "secret",
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_app_only_bearer_token_cached() {
+        let server = MockServer::start().await.unwrap();
+        let client = client(&server);
+        let (first, second) = tokio::join!(client.bearer_token(), client.bearer_token());
+        assert_eq!(first.unwrap().access_token.expose(), "mock_bearer_token_0");
+        assert_eq!(second.unwrap().access_token.expose(), "mock_bearer_token_0");
+        let cached = client.bearer_token().await.unwrap();
+        assert_eq!(cached.access_token.expose(), "mock_bearer_token_0");
+        assert_eq!(server.requests(MockEndpoint::AppOnlyToken), 1);
+    }
+
+    #[tokio::test]
+    async fn test_app_only_invalidate() {
+        let server = MockServer::start().await.unwrap();
+        let client = client(&server);
+        assert!(matches!(
+            client.invalidate_token().await,
+            Err(Error::Invalid(_))
+        ));
+        client.bearer_token().await.unwrap();
+        let (res, _, _) = client.invalidate_token().await.unwrap();
+        assert_eq!(res.access_token.expose(), "mock_bearer_token_0");
+        assert_eq!(server.requests(MockEndpoint::AppOnlyInvalidateToken), 1);
+
+        // the cache was cleared, so a new token is requested
+        let token = client.bearer_token().await.unwrap();
+        assert_eq!(token.access_token.expose(), "mock_bearer_token_1");
+
+        // invalidating another token keeps the cache
+        client.invalidate("other").await.unwrap();
+        client.bearer_token().await.unwrap();
+        assert_eq!(server.requests(MockEndpoint::AppOnlyToken), 2);
+    }
+}
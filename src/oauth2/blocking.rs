@@ -0,0 +1,155 @@
+use reqwest::{StatusCode, header::HeaderMap};
+
+use crate::{Json, blocking::execute_retry, error::Error};
+
+use super::{OAuth2Client, Scope, TokenResult, XClient, apply_client_auth, client_auth_params};
+
+impl<S: Scope> OAuth2Client<S> {
+    /// Blocking `token`. Must not be called from within an async runtime.
+    pub fn token_blocking(
+        &self,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        self.token_request_blocking("token", &params)
+    }
+
+    /// Blocking `refresh_token`. Must not be called from within an async runtime.
+    pub fn refresh_token_blocking(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        let params = [
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", self.client_id.as_str()),
+        ];
+        self.token_request_blocking("refresh_token", &params)
+    }
+
+    fn token_request_blocking(
+        &self,
+        endpoint: &'static str,
+        params: &[(&str, &str)],
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        let params =
+            client_auth_params(params, self.client_secret.expose(), self.client_auth_method);
+
+        let client = reqwest::blocking::Client::new();
+
+        execute_retry(
+            endpoint,
+            Json,
+            || {
+                apply_client_auth(
+                    client
+                        .post(&self.endpoints.token_url)
+                        .form(&params)
+                        .timeout(self.timeout),
+                    &self.client_id,
+                    self.client_secret.expose(),
+                    self.client_auth_method,
+                )
+            },
+            self.try_count,
+            self.retry_duration,
+        )
+    }
+}
+
+impl XClient {
+    pub fn token_blocking(
+        &self,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        self.inner.token_blocking(code, code_verifier)
+    }
+
+    pub fn refresh_token_blocking(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        self.inner.refresh_token_blocking(refresh_token)
+    }
+}
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        CallLimits,
+        error::Error,
+        mock_server::{MockEndpoint, MockFailure, MockServer},
+        oauth2::{XClient, XScope},
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_token_blocking() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(
+            MockEndpoint::Token,
+            MockFailure::Status(reqwest::StatusCode::SERVICE_UNAVAILABLE),
+        );
+        let client = XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            XScope::all(),
+            3,
+            Duration::from_millis(10),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap();
+        let (token, refreshed) = tokio::task::spawn_blocking(move || {
+            let (token, _, _) = client.token_blocking("code", "verifier").unwrap();
+            let (refreshed, _, _) = client
+                .refresh_token_blocking(token.refresh_token.as_ref().unwrap().expose())
+                .unwrap();
+            (token, refreshed)
+        })
+        .await
+        .unwrap();
+        assert_eq!(token.access_token.expose(), "mock_access_token_1");
+        assert_eq!(refreshed.access_token.expose(), "mock_access_token_2");
+        assert_eq!(server.requests(MockEndpoint::Token), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_token_blocking_deadline() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(
+            MockEndpoint::Token,
+            MockFailure::Status(reqwest::StatusCode::SERVICE_UNAVAILABLE),
+        );
+        let client = XClient::new_with_token_options(
+            "client",
+            "secret",
+            "http://localhost/callback",
+            XScope::all(),
+            3,
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+            Some(server.url().to_owned()),
+        )
+        .unwrap();
+        let res = tokio::task::spawn_blocking(move || {
+            CallLimits::new()
+                .with_deadline(Duration::from_millis(200))
+                .run_blocking(|| client.token_blocking("code", "verifier"))
+        })
+        .await
+        .unwrap();
+        assert!(matches!(res, Err(Error::DeadlineExceeded(_))));
+        assert_eq!(server.requests(MockEndpoint::Token), 1);
+    }
+}
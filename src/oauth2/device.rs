@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use reqwest::{StatusCode, header::HeaderMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Secret,
+    error::Error,
+    recorder::DEVICE_TOKEN_ENDPOINT,
+    runtime::{self, Instant},
+};
+
+use super::{OAuth2Client, Scope, TokenResult, XClient, scopes_to_string};
+
+pub const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+// RFC 8628 3.5: interval MUST be increased by 5 seconds on slow_down
+const SLOW_DOWN_INCREMENT: Duration = Duration::from_secs(5);
+
+fn default_interval() -> u64 {
+    5
+}
+
+/// Device Authorization Response (RFC 8628 3.2).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceAuthorizationResult {
+    pub device_code: Secret<String>,
+    pub user_code: String,
+    pub verification_uri: String,
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl<S: Scope> OAuth2Client<S> {
+    /// Requests a device code and a user code for the Device Authorization Grant.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.client_id)))]
+    pub async fn device_authorization(
+        &self,
+    ) -> Result<(DeviceAuthorizationResult, StatusCode, HeaderMap), Error> {
+        let Some(url) = self.endpoints.device_authorization_url() else {
+            return Err(Error::Invalid(
+                "device_authorization_url is not configured".to_owned(),
+            ));
+        };
+        let scopes_str = scopes_to_string(&self.scopes);
+        let params = [
+            ("client_id", self.client_id.as_str()),
+            ("scope", scopes_str.as_str()),
+        ];
+        self.token_request("device_authorization", url, &params)
+            .await
+    }
+
+    /// Polls the token endpoint until the user approves or denies the device.
+    ///
+    /// `authorization_pending` keeps polling every `interval` seconds and
+    /// `slow_down` increases the interval by 5 seconds. Any other error is
+    /// returned as is, and `Error::DeadlineExceeded` is returned once `expires_in` elapses.
+    /// With the `metrics` feature, those two polls count as outcome `pending`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.client_id)))]
+    pub async fn device_token(
+        &self,
+        device: &DeviceAuthorizationResult,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        let params = [
+            ("grant_type", DEVICE_CODE_GRANT_TYPE),
+            ("device_code", device.device_code.expose().as_str()),
+            ("client_id", self.client_id.as_str()),
+        ];
+        let expires_in = Duration::from_secs(device.expires_in);
+        let expires_at = Instant::now() + expires_in;
+        let mut interval = Duration::from_secs(device.interval);
+        loop {
+            runtime::sleep(interval).await;
+            if Instant::now() >= expires_at {
+                return Err(Error::DeadlineExceeded(expires_in));
+            }
+            match self
+                .token_request(DEVICE_TOKEN_ENDPOINT, &self.endpoints.token_url, &params)
+                .await
+            {
+                Err(Error::ClientError(body, status, headers)) => {
+                    let error = serde_json::from_str::<ErrorResponse>(&body)
+                        .map(|it| it.error)
+                        .unwrap_or_default();
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(error = %error, interval_secs = interval.as_secs(), "device token polling");
+                    match error.as_str() {
+                        "authorization_pending" => {}
+                        "slow_down" => interval += SLOW_DOWN_INCREMENT,
+                        _ => return Err(Error::ClientError(body, status, headers)),
+                    }
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl XClient {
+    /// X does not offer the Device Authorization Grant, so this returns
+    /// `Error::Invalid` with `OAuth2Endpoints::x()`. Set a
+    /// `device_authorization_url` on the endpoints to use it.
+    pub async fn device_authorization(
+        &self,
+    ) -> Result<(DeviceAuthorizationResult, StatusCode, HeaderMap), Error> {
+        self.inner.device_authorization().await
+    }
+
+    pub async fn device_token(
+        &self,
+        device: &DeviceAuthorizationResult,
+    ) -> Result<(TokenResult, StatusCode, HeaderMap), Error> {
+        self.inner.device_token(device).await
+    }
+}
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests {
+    use super::*;
+    use crate::{
+        CallLimits,
+        mock_server::{MockEndpoint, MockFailure, MockServer},
+        oauth2::OAuth2Endpoints,
+    };
+
+    fn client(server: &MockServer) -> OAuth2Client<String> {
+        OAuth2Client::new_public(
+            "client",
+            "http://localhost/callback",
+            vec!["read".to_owned(), "write".to_owned()],
+            OAuth2Endpoints::new(
+                &format!("{}/authorize", server.url()),
+                &format!("{}/2/oauth2/token", server.url()),
+            )
+            .unwrap()
+            .with_device_authorization_url(&format!(
+                "{}/2/oauth2/device_authorization",
+                server.url()
+            ))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_device_flow() {
+        let server = MockServer::start().await.unwrap();
+        server.fail_times(MockEndpoint::Token, MockFailure::AuthorizationPending, 2);
+        let client = client(&server);
+        let (device, _, _) = client.device_authorization().await.unwrap();
+        assert_eq!(device.user_code, "MOCK-CODE");
+        let request = server
+            .last_request(MockEndpoint::DeviceAuthorization)
+            .unwrap();
+        assert_eq!(request.params.get("scope").unwrap(), "read write");
+
+        let (token, _, _) = client.device_token(&device).await.unwrap();
+        assert_eq!(token.access_token.expose(), "mock_access_token_2");
+        assert_eq!(server.requests(MockEndpoint::Token), 3);
+        let request = server.last_request(MockEndpoint::Token).unwrap();
+        assert_eq!(
+            request.params.get("grant_type").unwrap(),
+            DEVICE_CODE_GRANT_TYPE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_device_flow_denied() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(MockEndpoint::Token, MockFailure::AccessDenied);
+        let client = client(&server);
+        let (device, _, _) = client.device_authorization().await.unwrap();
+        let res = client.device_token(&device).await;
+        assert!(
+            matches!(res, Err(Error::ClientError(body, _, _)) if body.contains("access_denied"))
+        );
+        assert_eq!(server.requests(MockEndpoint::Token), 1);
+    }
+
+    #[tokio::test]
+    async fn test_device_code_expired() {
+        let server = MockServer::start().await.unwrap();
+        let client = client(&server);
+        let (mut device, _, _) = client.device_authorization().await.unwrap();
+        device.expires_in = 0;
+        let res = client.device_token(&device).await;
+        assert!(matches!(res, Err(Error::DeadlineExceeded(_))));
+        assert_eq!(server.requests(MockEndpoint::Token), 0);
+    }
+
+    #[tokio::test]
+    async fn test_device_token_slow_down() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(MockEndpoint::Token, MockFailure::AuthorizationPending);
+        server.fail(MockEndpoint::Token, MockFailure::SlowDown);
+        let client = client(&server);
+        let (device, _, _) = client.device_authorization().await.unwrap();
+        assert_eq!(device.interval, 0);
+
+        let started = std::time::Instant::now();
+        let (token, _, _) = client.device_token(&device).await.unwrap();
+        // pending and the first slow_down are polled at once, then the interval is 0 + 5 seconds
+        assert!(started.elapsed() >= Duration::from_secs(5));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(token.access_token.expose(), "mock_access_token_2");
+        assert_eq!(server.requests(MockEndpoint::Token), 3);
+        let request = server.last_request(MockEndpoint::Token).unwrap();
+        assert_eq!(
+            request.params.get("device_code").unwrap(),
+            "mock_device_code"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_device_token_deadline() {
+        let server = MockServer::start().await.unwrap();
+        server.fail(MockEndpoint::Token, MockFailure::SlowDown);
+        let client = client(&server);
+        let (device, _, _) = client.device_authorization().await.unwrap();
+
+        let started = std::time::Instant::now();
+        let res = CallLimits::new()
+            .with_deadline(Duration::from_millis(300))
+            .run(client.device_token(&device))
+            .await;
+        assert!(matches!(res, Err(Error::DeadlineExceeded(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.requests(MockEndpoint::Token), 1);
+    }
+}
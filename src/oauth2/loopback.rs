@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use crate::{error::Error, loopback::LoopbackListener};
+
+use super::{OAuth2Client, Scope, TokenResult, XClient, random_state};
+
+impl<S: Scope> OAuth2Client<S> {
+    /// Logs in through the browser for desktop and CLI apps.
+    ///
+    /// Listens on the loopback port of `redirect_uri`, passes the authorize
+    /// URL to `open_url`, waits up to `timeout` for the redirect, validates
+    /// `state` and exchanges the code with `token`. The host of
+    /// `redirect_uri` must be `127.0.0.1`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(client_id = %self.client_id)))]
+    pub async fn login_interactive(
+        &self,
+        timeout: Duration,
+        open_url: impl FnOnce(&str),
+    ) -> Result<TokenResult, Error> {
+        let listener = LoopbackListener::bind(&self.redirect_uri).await?;
+        let state = random_state();
+        let (url, code_verifier) = self.authorize_url(&state);
+        open_url(&url);
+
+        let params = listener.wait(timeout).await?;
+        if let Some(error) = params.get("error") {
+            return Err(Error::Invalid(format!("authorization error: {}", error)));
+        }
+        if params.get("state") != Some(&state) {
+            return Err(Error::Invalid("state mismatch".to_owned()));
+        }
+        let Some(code) = params.get("code") else {
+            return Err(Error::Invalid("code is missing".to_owned()));
+        };
+        let (token, _, _) = self.token(code, &code_verifier).await?;
+        Ok(token)
+    }
+}
+
+impl XClient {
+    pub async fn login_interactive(
+        &self,
+        timeout: Duration,
+        open_url: impl FnOnce(&str),
+    ) -> Result<TokenResult, Error> {
+        self.inner.login_interactive(timeout, open_url).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Form, Json, Router, routing::post};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        CallLimits, CancellationToken,
+        oauth2::{XClient, XScope},
+    };
+
+    async fn token(Form(params): Form<Vec<(String, String)>>) -> Json<Value> {
+        assert!(params.contains(&("code".to_owned(), "auth_code".to_owned())));
+        Json(json!({
+            "access_token": "access",
+            "refresh_token": "refresh",
+            "expires_in": 7200,
+            "scope": "tweet.read",
+            "token_type": "bearer",
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_login_interactive() {
+        let token_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let prefix_url = format!("http://{}", token_listener.local_addr().unwrap());
+        let app = Router::new().route("/2/oauth2/token", post(token));
+        tokio::spawn(async move { axum::serve(token_listener, app).await.unwrap() });
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+        let client = XClient::new_with_token_options(
+            "client",
+            "secret",
+            &redirect_uri,
+            vec![XScope::TweetRead],
+            1,
+            Duration::from_millis(100),
+            Duration::from_secs(10),
+            Some(prefix_url),
+        )
+        .unwrap();
+
+        let token = client
+            .login_interactive(Duration::from_secs(10), |url| {
+                let url = reqwest::Url::parse(url).unwrap();
+                let state = url
+                    .query_pairs()
+                    .find(|(key, _)| key == "state")
+                    .unwrap()
+                    .1
+                    .into_owned();
+                let callback = format!("{}?code=auth_code&state={}", redirect_uri, state);
+                tokio::spawn(async move {
+                    let res = reqwest::get(callback).await.unwrap();
+                    assert!(res.status().is_success());
+                });
+            })
+            .await
+            .unwrap();
+        assert_eq!(token.access_token.expose(), "access");
+    }
+
+    #[tokio::test]
+    async fn test_login_interactive_cancelled() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+        let client = XClient::new("client", "secret", &redirect_uri, vec![XScope::TweetRead]);
+        let token = CancellationToken::new();
+        let limits = CallLimits::new().with_cancellation_token(token.clone());
+
+        let res = limits
+            .run(client.login_interactive(Duration::from_secs(10), |_| token.cancel()))
+            .await;
+        assert!(matches!(res, Err(Error::Cancelled)));
+        // the listener is released with the cancelled call
+        std::net::TcpListener::bind(("127.0.0.1", port)).unwrap();
+    }
+}
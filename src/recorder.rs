@@ -0,0 +1,157 @@
+use crate::{error::Error, runtime::Instant};
+
+#[cfg(feature = "metrics")]
+const REQUESTS_TOTAL: &str = "twapi_oauth2_requests_total";
+#[cfg(feature = "metrics")]
+const REQUEST_DURATION_SECONDS: &str = "twapi_oauth2_request_duration_seconds";
+#[cfg(feature = "metrics")]
+const RETRIES_TOTAL: &str = "twapi_oauth2_retries_total";
+#[cfg(feature = "metrics")]
+const ERRORS_TOTAL: &str = "twapi_oauth2_errors_total";
+
+#[cfg(any(feature = "metrics", feature = "oauth2"))]
+pub(crate) const DEVICE_TOKEN_ENDPOINT: &str = "device_token";
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_retry(endpoint: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(RETRIES_TOTAL, "endpoint" => endpoint).increment(1);
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_result<T>(endpoint: &'static str, started: Instant, res: &Result<T, Error>) {
+    #[cfg(feature = "metrics")]
+    {
+        let outcome = match res {
+            Ok(_) => "success",
+            Err(err) if endpoint == DEVICE_TOKEN_ENDPOINT && is_device_pending(err) => "pending",
+            Err(_) => "error",
+        };
+        metrics::counter!(REQUESTS_TOTAL, "endpoint" => endpoint, "outcome" => outcome)
+            .increment(1);
+        metrics::histogram!(REQUEST_DURATION_SECONDS, "endpoint" => endpoint)
+            .record(started.elapsed().as_secs_f64());
+        if let (Err(err), "error") = (res, outcome) {
+            metrics::counter!(ERRORS_TOTAL, "endpoint" => endpoint, "class" => error_class(err))
+                .increment(1);
+        }
+    }
+}
+
+/// `authorization_pending` and `slow_down` are expected while the user has
+/// not approved the device yet.
+#[cfg(feature = "metrics")]
+fn is_device_pending(err: &Error) -> bool {
+    matches!(err, Error::ClientError(body, http::StatusCode::BAD_REQUEST, _)
+        if body.contains("authorization_pending") || body.contains("slow_down"))
+}
+
+#[cfg(feature = "metrics")]
+fn error_class(err: &Error) -> &'static str {
+    match err {
+        Error::Reqwest(e) if e.is_decode() => "decode",
+        Error::Reqwest(e) if e.is_timeout() => "timeout",
+        Error::Reqwest(_) => "transport",
+        Error::Invalid(_) => "invalid",
+        Error::ClientError(..) if err.is_rate_limited() => "rate_limited",
+        Error::ClientError(..) => "client_error",
+        Error::RetryOver(..) => "retry_over",
+        Error::Decode { .. } => "decode",
+        Error::DeadlineExceeded(_) => "deadline_exceeded",
+        Error::Cancelled => "cancelled",
+    }
+}
+
+#[cfg(all(test, feature = "metrics", feature = "mock-server", feature = "oauth2"))]
+mod tests {
+    use super::*;
+    use metrics_util::{
+        CompositeKey, MetricKind,
+        debugging::{DebugValue, DebuggingRecorder},
+    };
+
+    use crate::{
+        mock_server::{MockEndpoint, MockFailure, MockServer},
+        oauth2::{OAuth2Client, OAuth2Endpoints},
+    };
+
+    fn counter(
+        snapshot: &[(CompositeKey, DebugValue)],
+        name: &str,
+        labels: &[(&str, &str)],
+    ) -> u64 {
+        snapshot
+            .iter()
+            .filter(|(key, _)| key.kind() == MetricKind::Counter && key.key().name() == name)
+            .filter(|(key, _)| {
+                labels.iter().all(|(label, value)| {
+                    key.key()
+                        .labels()
+                        .any(|it| it.key() == *label && it.value() == *value)
+                })
+            })
+            .map(|(_, value)| match value {
+                DebugValue::Counter(value) => *value,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_device_pending_is_not_an_error() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let server = MockServer::start().await.unwrap();
+                server.fail_times(MockEndpoint::Token, MockFailure::AuthorizationPending, 2);
+                let client = OAuth2Client::new_public(
+                    "client",
+                    "http://localhost/callback",
+                    vec!["tweet.read".to_owned()],
+                    OAuth2Endpoints::new(
+                        &format!("{}/authorize", server.url()),
+                        &format!("{}/2/oauth2/token", server.url()),
+                    )
+                    .unwrap()
+                    .with_device_authorization_url(&format!(
+                        "{}/2/oauth2/device_authorization",
+                        server.url()
+                    ))
+                    .unwrap(),
+                );
+                let (device, _, _) = client.device_authorization().await.unwrap();
+                client.device_token(&device).await.unwrap();
+            })
+        });
+
+        let snapshot: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key, value))
+            .collect();
+        let requests = |outcome| {
+            counter(
+                &snapshot,
+                REQUESTS_TOTAL,
+                &[("endpoint", DEVICE_TOKEN_ENDPOINT), ("outcome", outcome)],
+            )
+        };
+        assert_eq!(requests("pending"), 2);
+        assert_eq!(requests("success"), 1);
+        assert_eq!(requests("error"), 0);
+        assert_eq!(
+            counter(
+                &snapshot,
+                ERRORS_TOTAL,
+                &[("endpoint", DEVICE_TOKEN_ENDPOINT)]
+            ),
+            0
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use reqwest::{RequestBuilder, StatusCode, header::HeaderMap};
+
+use crate::{
+    error::Error,
+    recorder,
+    runtime::{self, Instant},
+};
+
+/// Decodes the body of a successful response.
+#[allow(dead_code)]
+pub(crate) trait Decoder<T> {
+    fn decode(&self, body: &str) -> Result<T, DecodeError>;
+}
+
+pub(crate) type DecodeError = Box<dyn std::error::Error + Send + Sync>;
+
+/// JSON body.
+#[allow(dead_code)]
+pub(crate) struct Json;
+
+impl<T: serde::de::DeserializeOwned> Decoder<T> for Json {
+    fn decode(&self, body: &str) -> Result<T, DecodeError> {
+        Ok(serde_json::from_str(body)?)
+    }
+}
+
+/// `application/x-www-form-urlencoded` body, as returned by the OAuth 1.0a endpoints.
+#[cfg(feature = "oauth1a")]
+pub(crate) struct Form;
+
+#[cfg(feature = "oauth1a")]
+impl<T: serde::de::DeserializeOwned> Decoder<T> for Form {
+    fn decode(&self, body: &str) -> Result<T, DecodeError> {
+        Ok(serde_urlencoded::from_str(body)?)
+    }
+}
+
+/// Raw text body.
+#[allow(dead_code)]
+pub(crate) struct Text;
+
+impl Decoder<String> for Text {
+    fn decode(&self, body: &str) -> Result<String, DecodeError> {
+        Ok(body.to_owned())
+    }
+}
+
+/// Sends the request built by `f`, retrying 5xx responses with exponential
+/// backoff, and decodes a successful body with `decoder`.
+#[allow(dead_code)]
+pub(crate) async fn execute_retry<T>(
+    endpoint: &'static str,
+    decoder: impl Decoder<T>,
+    f: impl Fn() -> RequestBuilder,
+    try_count: usize,
+    retry_duration: Duration,
+) -> Result<(T, StatusCode, HeaderMap), Error> {
+    let started = Instant::now();
+    let attempts = async {
+        for i in 0..try_count {
+            let res = f().send().await.map_err(|e| send_error(i, e))?;
+            let status = res.status();
+            let headers = res.headers().clone();
+            match next_step(endpoint, i, try_count, retry_duration, status) {
+                Step::Retry(delay) => runtime::sleep(delay).await,
+                step => return finish(&decoder, step, res.text().await, status, headers),
+            }
+        }
+        unreachable!()
+    };
+    let res = attempts.await;
+    recorder::record_result(endpoint, started, &res);
+    res
+}
+
+/// What to do with the response to an attempt.
+pub(crate) enum Step {
+    Success,
+    ClientError,
+    Retry(Duration),
+    RetryOver,
+}
+
+/// Classifies the response to attempt `i`, recording a retry when one follows.
+pub(crate) fn next_step(
+    endpoint: &'static str,
+    i: usize,
+    try_count: usize,
+    retry_duration: Duration,
+    status: StatusCode,
+) -> Step {
+    if status.is_success() {
+        return Step::Success;
+    } else if status.is_client_error() {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(attempt = i + 1, status = status.as_u16(), "client error");
+        return Step::ClientError;
+    }
+    if i + 1 < try_count {
+        recorder::record_retry(endpoint);
+        let retry_duration = backoff(i, retry_duration);
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            attempt = i + 1,
+            try_count,
+            status = status.as_u16(),
+            delay_ms = retry_duration.as_millis() as u64,
+            "retrying request"
+        );
+        Step::Retry(retry_duration)
+    } else {
+        #[cfg(feature = "tracing")]
+        tracing::error!(
+            attempt = i + 1,
+            try_count,
+            status = status.as_u16(),
+            "retry over"
+        );
+        Step::RetryOver
+    }
+}
+
+/// Turns the body of a response that is not retried into the result of the call.
+pub(crate) fn finish<T>(
+    decoder: &impl Decoder<T>,
+    step: Step,
+    body: Result<String, reqwest::Error>,
+    status: StatusCode,
+    headers: HeaderMap,
+) -> Result<(T, StatusCode, HeaderMap), Error> {
+    match step {
+        Step::Success => decode(decoder, body?, status, headers),
+        Step::ClientError => Err(Error::ClientError(
+            body.unwrap_or_default(),
+            status,
+            Box::new(headers),
+        )),
+        Step::RetryOver => Err(Error::RetryOver(
+            body.unwrap_or_default(),
+            status,
+            Box::new(headers),
+        )),
+        Step::Retry(_) => unreachable!("a retried attempt has no result"),
+    }
+}
+
+/// Error of attempt `i` that failed before a response arrived.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn send_error(i: usize, e: reqwest::Error) -> Error {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(attempt = i + 1, error = %e, "request failed");
+    e.into()
+}
+
+fn decode<T>(
+    decoder: &impl Decoder<T>,
+    body: String,
+    status: StatusCode,
+    headers: HeaderMap,
+) -> Result<(T, StatusCode, HeaderMap), Error> {
+    match decoder.decode(&body) {
+        Ok(value) => Ok((value, status, headers)),
+        Err(source) => Err(Error::Decode {
+            body,
+            status,
+            headers: Box::new(headers),
+            source,
+        }),
+    }
+}
+
+/// Delay before the attempt after attempt `i`.
+fn backoff(i: usize, retry_duration: Duration) -> Duration {
+    // ジッターとエクスポーネンシャルバックオフを組み合わせる
+    let jitter: u64 = rand::random::<u64>() % retry_duration.as_millis() as u64;
+    let exp_backoff = 2u64.pow(i as u32) * retry_duration.as_millis() as u64;
+    Duration::from_millis(exp_backoff + jitter)
+}
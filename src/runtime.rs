@@ -0,0 +1,49 @@
+//! Timers used for backoff, deadlines and polling. tokio's timer is used
+//! unless only `rt-futures-timer` is enabled, which switches to futures-timer.
+//! That is meant for `wasm32-unknown-unknown` together with `wasm-js`: it only
+//! swaps the timer, and reqwest's native client still needs a tokio runtime.
+
+use std::{
+    future::{Future, poll_fn},
+    pin::pin,
+    task::Poll,
+    time::Duration,
+};
+
+pub(crate) use web_time::Instant;
+
+#[cfg(any(feature = "rt-tokio", not(feature = "rt-futures-timer")))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(all(feature = "rt-futures-timer", not(feature = "rt-tokio")))]
+pub(crate) async fn sleep(duration: Duration) {
+    futures_timer::Delay::new(duration).await
+}
+
+/// Resolves `future`, or `None` if `duration` elapses first.
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    let mut future = pin!(future);
+    let mut sleep = pin!(sleep(duration));
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        sleep.as_mut().poll(cx).map(|_| None)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let fast = timeout(Duration::from_secs(1), async { 1 }).await;
+        assert_eq!(fast, Some(1));
+        let slow = timeout(Duration::from_millis(10), sleep(Duration::from_secs(10))).await;
+        assert_eq!(slow, None);
+    }
+}